@@ -1,3 +0,0 @@
-#! /bin/bash
-
-rm -v *.png
\ No newline at end of file
@@ -0,0 +1,419 @@
+use flux::prelude::*;
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg;
+use std::env;
+use std::fs;
+use std::process::exit;
+
+const USAGE: &str = "usage: assets [options]
+
+Converts the master textures (SVG, or PNG larger than a block) to block-sized PNGs,
+and checks that every surface in the palette has a diffuse and height map of the same size.
+Only masters newer than their PNG are converted.
+Built with the assets feature: cargo run --features assets --bin assets -- [options]
+
+options:
+  --textures <dir>                 output directory (default assets/textures)
+  --master <dir>                   master textures (default: <textures>/master)
+  --force                          convert all masters, even if their PNG is up to date
+  --check                          only check the masters, do not convert
+  --clean                          remove all PNGs converted from a master
+";
+
+struct Args {
+	textures: PathBuf,
+	master: Option<PathBuf>,
+	force: bool,
+	check: bool,
+	clean: bool,
+}
+
+fn main() {
+	let args = match parse_args(env::args().skip(1)) {
+		Ok(Some(args)) => args,
+		Ok(None) => {
+			print!("{}", USAGE);
+			return;
+		}
+		Err(e) => {
+			eprintln!("{}\n\n{}", e, USAGE);
+			exit(2);
+		}
+	};
+	match run(&args) {
+		Ok(true) => (),
+		Ok(false) => exit(1),
+		Err(e) => {
+			eprintln!("error: {}", e);
+			exit(1);
+		}
+	}
+}
+
+// Returns false if there were problems that were reported.
+fn run(args: &Args) -> Result<bool> {
+	let dir = &args.textures;
+	let master_dir = args.master.clone().unwrap_or_else(|| dir.join("master"));
+	let masters = list_masters(&master_dir)?;
+
+	if args.clean {
+		for m in &masters {
+			let png = dir.join(m.png_name());
+			if png.exists() {
+				fs::remove_file(&png)?;
+				println!("removed {}", png.to_string_lossy());
+			}
+		}
+		return Ok(true);
+	}
+
+	let report = check_masters(&masters);
+	for o in &report.orphans {
+		println!("warning: {}", o);
+	}
+	for e in &report.errors {
+		eprintln!("error: {}", e);
+	}
+	if args.check {
+		return Ok(report.is_ok());
+	}
+
+	let mut ok = report.is_ok();
+	for m in &masters {
+		let png = dir.join(m.png_name());
+		if !args.force && !is_newer(&master_dir.join(&m.file), &png) {
+			continue;
+		}
+		match m.convert(&master_dir, dir) {
+			Ok(()) => println!("wrote {}", png.to_string_lossy()),
+			Err(e) => {
+				eprintln!("error: {}: {}", m.file, e);
+				ok = false;
+			}
+		}
+	}
+	Ok(ok)
+}
+
+// All SVG and PNG files in dir, sorted by name.
+fn list_masters(dir: &Path) -> Result<Vec<Master>> {
+	check_exists(dir)?;
+	let mut files = Vec::new();
+	for entry in fs::read_dir(dir)? {
+		let path = entry?.path();
+		match path.extension().and_then(|e| e.to_str()) {
+			Some("svg") | Some("png") => files.push(path),
+			_ => (),
+		}
+	}
+	files.sort();
+	files.iter().map(|p| Master::open(p)).collect()
+}
+
+// Is src modified after dst (or does dst not exist)?
+fn is_newer(src: &Path, dst: &Path) -> bool {
+	let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+	match (modified(src), modified(dst)) {
+		(Some(s), Some(d)) => s > d,
+		_ => true,
+	}
+}
+
+// Parse command-line arguments (without the program name), None means --help.
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Args>> {
+	let mut a = Args {
+		textures: PathBuf::from(DEFAULT_TEXTURE_DIR),
+		master: None,
+		force: false,
+		check: false,
+		clean: false,
+	};
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		let mut value = || match args.next() {
+			Some(v) => Ok(v),
+			None => GenError::new(format!("{} needs a value", arg)),
+		};
+		match arg.as_str() {
+			"--textures" => a.textures = PathBuf::from(value()?),
+			"--master" => a.master = Some(PathBuf::from(value()?)),
+			"--force" => a.force = true,
+			"--check" => a.check = true,
+			"--clean" => a.clean = true,
+			"-h" | "--help" => return Ok(None),
+			_ => return GenError::new(format!("unexpected argument: {}", arg)),
+		}
+	}
+	Ok(Some(a))
+}
+
+/// A source texture in the master directory:
+/// an SVG, or a PNG that is larger than GRID.
+/// Surfaces come as a pair of maps named <srf>.dm.svg and <srf>.hm.svg,
+/// other files (e.g. editor icons) are converted but not checked against the palette.
+#[derive(Clone, Debug, PartialEq)]
+struct Master {
+	/// File name, e.g. "seed.dm.svg".
+	file: String,
+	/// Width and height in the master's own units (SVG user units or pixels).
+	size: (f32, f32),
+}
+
+/// Which map of a surface a master is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum MapKind {
+	Diffuse,
+	Height,
+}
+
+impl Master {
+	/// Parse the size of an SVG or PNG master file.
+	fn open(path: &Path) -> Result<Self> {
+		let file = match path.file_name() {
+			Some(f) => f.to_string_lossy().into_owned(),
+			None => return GenError::new(format!("not a file: {}", path.to_string_lossy())),
+		};
+		let size = match extension(&file) {
+			"svg" => {
+				let s = load_svg(path)?.size();
+				(s.width(), s.height())
+			}
+			"png" => {
+				let (w, h) = image::image_dimensions(path)?;
+				(w as f32, h as f32)
+			}
+			_ => return GenError::new(format!("not an SVG or PNG: {}", path.to_string_lossy())),
+		};
+		Ok(Self { file, size })
+	}
+
+	/// Surface name and map kind, e.g. ("seed", Diffuse) for "seed.dm.svg".
+	/// None if the master is not a surface map.
+	fn surface(&self) -> Option<(&str, MapKind)> {
+		let stem = &self.file[..self.file.len() - extension(&self.file).len()];
+		let stem = stem.trim_end_matches('.');
+		if let Some(srf) = stem.strip_suffix(".dm") {
+			Some((srf, MapKind::Diffuse))
+		} else {
+			stem.strip_suffix(".hm").map(|srf| (srf, MapKind::Height))
+		}
+	}
+
+	/// Name of the PNG converted from this master, e.g. "seed.dm.png".
+	fn png_name(&self) -> String {
+		let ext = extension(&self.file);
+		format!("{}png", &self.file[..self.file.len() - ext.len()])
+	}
+
+	/// Size of the converted PNG: GRID pixels wide, keeping the aspect ratio.
+	/// (SVG sizes are not exact, e.g. 159.8x160, but should convert to GRIDxGRID all the same).
+	fn png_size(&self) -> (u32, u32) {
+		let (w, h) = self.size;
+		(GRID as u32, max(1.0, (h * GRID as f32 / w).round()) as u32)
+	}
+
+	/// Rasterise (SVG) or rescale (PNG) to png_size() and save as dir/png_name().
+	/// Height maps are saved in grayscale.
+	fn convert(&self, master_dir: &Path, dir: &Path) -> Result<()> {
+		let src = master_dir.join(&self.file);
+		let img = match extension(&self.file) {
+			"svg" => rasterise_svg(&load_svg(&src)?, self.png_size()),
+			_ => rescale_png(&src, self.png_size())?,
+		};
+		let dst = dir.join(self.png_name());
+		match self.surface() {
+			Some((_, MapKind::Height)) => to_gray(&img).save(&dst)?,
+			_ => img.save(&dst)?,
+		}
+		Ok(())
+	}
+}
+
+/// Problems found by check_masters.
+#[derive(Default, Debug, PartialEq)]
+struct AssetReport {
+	/// Palette surfaces without a diffuse or height map, or with maps that do not match.
+	/// The textures of these blocks cannot be built.
+	errors: Vec<String>,
+	/// Surface maps not used by any block in the palette.
+	orphans: Vec<String>,
+}
+
+impl AssetReport {
+	fn is_ok(&self) -> bool {
+		self.errors.is_empty()
+	}
+}
+
+/// Check masters against ED_PALETTE: every surface must have
+/// a diffuse and a height map that convert to GRIDxGRID pixels.
+fn check_masters(masters: &[Master]) -> AssetReport {
+	let mut maps = FnvHashMap::<&str, (Option<&Master>, Option<&Master>)>::default();
+	for m in masters {
+		if let Some((srf, kind)) = m.surface() {
+			let e = maps.entry(srf).or_default();
+			match kind {
+				MapKind::Diffuse => e.0 = Some(m),
+				MapKind::Height => e.1 = Some(m),
+			}
+		}
+	}
+
+	let mut r = AssetReport::default();
+	let mut used = FnvHashSet::default();
+	for def in ED_PALETTE.iter().skip(1) {
+		if !used.insert(def.srf) {
+			continue;
+		}
+		match maps.get(def.srf) {
+			None => r.errors.push(format!("{}: no master textures", def.srf)),
+			Some((None, _)) => r.errors.push(format!("{}: missing diffuse map", def.srf)),
+			Some((_, None)) => r.errors.push(format!("{}: missing height map", def.srf)),
+			Some((Some(dm), Some(hm))) => {
+				let (d, h) = (dm.png_size(), hm.png_size());
+				if d != h {
+					r.errors.push(format!(
+						"{}: diffuse map is {}x{} pixels, height map is {}x{}",
+						def.srf, d.0, d.1, h.0, h.1
+					));
+				} else if d.0 != d.1 {
+					r.errors
+						.push(format!("{}: not square: {}x{} pixels", def.srf, d.0, d.1));
+				}
+			}
+		}
+	}
+	for m in masters {
+		if let Some((srf, _)) = m.surface() {
+			if !used.contains(srf) {
+				r.orphans.push(format!("{}: not used by any block", m.file));
+			}
+		}
+	}
+	r.errors.sort();
+	r.orphans.sort();
+	r
+}
+
+fn extension(file: &str) -> &str {
+	match file.rfind('.') {
+		Some(i) => &file[i + 1..],
+		None => "",
+	}
+}
+
+fn load_svg(path: &Path) -> Result<usvg::Tree> {
+	check_exists(path)?;
+	let data = fs::read(path)?;
+	match usvg::Tree::from_data(&data, &usvg::Options::default()) {
+		Ok(tree) => Ok(tree),
+		Err(e) => GenError::new(format!("{}: {}", path.to_string_lossy(), e)),
+	}
+}
+
+/// Render an SVG, stretched to (width, height) pixels.
+fn rasterise_svg(tree: &usvg::Tree, (width, height): (u32, u32)) -> Image<BGRA> {
+	let size = tree.size();
+	let (sx, sy) = (width as f32 / size.width(), height as f32 / size.height());
+	let mut pixmap = Pixmap::new(width, height).expect("svg size");
+	resvg::render(tree, Transform::from_scale(sx, sy), &mut pixmap.as_mut());
+	Image::from_fn((width as i32, height as i32), |x, y| {
+		let c = pixmap.pixel(x as u32, y as u32).unwrap().demultiply();
+		BGRA(c.blue(), c.green(), c.red(), c.alpha())
+	})
+}
+
+// Rescale a PNG to (width, height) pixels.
+fn rescale_png(path: &Path, (width, height): (u32, u32)) -> Result<Image<BGRA>> {
+	check_exists(path)?;
+	let src = image::open(path)?.into_rgba();
+	let dst = image::imageops::resize(&src, width, height, image::imageops::FilterType::Triangle);
+	Ok(Image::from_fn((width as i32, height as i32), |x, y| {
+		let c = dst.get_pixel(x as u32, y as u32);
+		BGRA(c[2], c[1], c[0], c[3])
+	}))
+}
+
+// Grayscale, transparent pixels are black (height 0).
+fn to_gray(img: &Image<BGRA>) -> Image<u8> {
+	Image::from_fn(img.dimensions(), |x, y| {
+		let c = img.at((x, y));
+		(c.luminance() * (c.a() as f32) / 255.0).round() as u8
+	})
+}
+
+#[test]
+fn test_master_names() {
+	let m = |file: &str| Master {
+		file: file.into(),
+		size: (64.0, 64.0),
+	};
+	assert_eq!(m("seed.dm.svg").surface(), Some(("seed", MapKind::Diffuse)));
+	assert_eq!(
+		m("fe-top.hm.png").surface(),
+		Some(("fe-top", MapKind::Height))
+	);
+	assert_eq!(m("pencil.svg").surface(), None);
+	assert_eq!(m("seed.dm.svg").png_name(), "seed.dm.png");
+	assert_eq!(m("hamster1.png").png_name(), "hamster1.png");
+
+	let mut m = m("silicon-top.dm.svg");
+	m.size = (159.8, 160.00002);
+	assert_eq!(m.png_size(), (GRID as u32, GRID as u32));
+	m.size = (128.0, 64.0);
+	assert_eq!(m.png_size(), (GRID as u32, GRID as u32 / 2));
+}
+
+#[test]
+fn test_check_masters() {
+	let m = |file: String, size: f32| Master {
+		file,
+		size: (size, size),
+	};
+	let mut masters = vec![m("pencil.svg".into(), 10.0)];
+	for def in ED_PALETTE.iter().skip(1) {
+		masters.push(m(format!("{}.dm.svg", def.srf), 64.0));
+		masters.push(m(format!("{}.hm.svg", def.srf), 64.0));
+	}
+	assert_eq!(check_masters(&masters), AssetReport::default());
+
+	masters.retain(|m| m.file != "seed.hm.svg" && m.file != "ladder.dm.svg");
+	masters.push(m("orphan.hm.png".into(), 64.0));
+	for m in &mut masters {
+		if m.file == "lava.hm.svg" {
+			m.size = (64.0, 128.0);
+		}
+		// masters need not be GRID wide, errors show the converted size.
+		if m.file.starts_with("ice.") {
+			m.size = (128.0, 64.0);
+		}
+	}
+	let r = check_masters(&masters);
+	assert!(!r.is_ok());
+	assert_eq!(
+		r.errors,
+		vec![
+			"ice: not square: 64x32 pixels",
+			"ladder: missing diffuse map",
+			"lava: diffuse map is 64x64 pixels, height map is 64x128",
+			"seed: missing height map",
+		]
+	);
+	assert_eq!(r.orphans, vec!["orphan.hm.png: not used by any block"]);
+}
+
+#[test]
+fn test_rasterise_svg() {
+	// left half white, right half transparent.
+	let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="128" height="64">
+		<rect x="0" y="0" width="64" height="64" fill="#ffffff"/>
+	</svg>"##;
+	let tree = usvg::Tree::from_data(svg, &usvg::Options::default()).unwrap();
+	let img = rasterise_svg(&tree, (16, 8));
+	assert_eq!(img.dimensions(), (16, 8));
+	assert_eq!(img.at((2, 4)), BGRA(255, 255, 255, 255));
+	assert_eq!(img.at((13, 4)).a(), 0);
+
+	let gray = to_gray(&img);
+	assert_eq!((gray.at((2, 4)), gray.at((13, 4))), (255, 0));
+}
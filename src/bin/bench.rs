@@ -1,29 +1,244 @@
-//use flux::prelude::*;
-//use std::time::Instant;
-
-fn main() {}
-
-//fn render() {
-//	let pal = default_palette();
-//	let lights = Lights::new();
-//	//lights.ambient_rays = 0;
-//	//lights.sun_rays = 1;
-//	let mut b = Bakery::new(pal, lights);
-//
-//	for i in 0..NUM_BLOCKS {
-//		let i = i as u8;
-//		let start = now();
-//		let key = [[0, 0, 0], [0, i, 0], [0, 0, 0]];
-//		b.send(key);
-//		b.recv(key);
-//		report_since(&format!("render {}:", i), start);
-//	}
-//}
-
-//fn now() -> Instant {
-//	Instant::now()
-//}
-//
-//fn report_since(name: &str, start: Instant) {
-//	println!("{}: {} s", name, start.elapsed().as_secs_f32());
-//}
+use flux::prelude::*;
+use serde::Serialize;
+use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+const USAGE: &str = "usage: bench [options]
+
+Bakes a set of tiles and reports tiles/s, rays/s and CPU time per thread.
+
+options:
+  --keys <json>                    tiles to bake, a JSON array of TileKeys
+                                   (default: every block, alone and with neighbors)
+  --lights <json>                  Lights to bake with (default: Lights::new())
+  --textures <dir>                 load textures from dir (default assets/textures)
+  --threads <n>                    number of threads (default: one per core)
+  --repeat <n>                     bake all tiles n times (default 1)
+  --marcher <scalar|packet>        how shadow rays are traced (default packet)
+  --compare                        also bake with the scalar marcher, report the speed-up
+  --json <file>                    write the results as JSON
+";
+
+struct Args {
+	keys: Option<PathBuf>,
+	textures: PathBuf,
+	lights: Option<PathBuf>,
+	threads: usize,
+	repeat: usize,
+	marcher: Marcher,
+	compare: bool,
+	json: Option<PathBuf>,
+}
+
+/// Results of baking all tiles once with one Marcher (written as JSON).
+#[derive(Serialize)]
+struct Run {
+	marcher: Marcher,
+	threads: usize,
+	tiles: usize,
+	rays: usize,
+	secs: f64,
+	tiles_per_sec: f64,
+	rays_per_sec: f64,
+	/// CPU time spent baking, per thread (not counting time preempted or waiting).
+	thread_cpu_secs: Vec<f64>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+	lights: &'a Lights,
+	runs: Vec<Run>,
+}
+
+fn main() {
+	let args = match parse_args(env::args().skip(1)) {
+		Ok(Some(args)) => args,
+		Ok(None) => {
+			print!("{}", USAGE);
+			return;
+		}
+		Err(e) => {
+			eprintln!("{}\n\n{}", e, USAGE);
+			exit(2);
+		}
+	};
+	if let Err(e) = run(&args) {
+		eprintln!("error: {}", e);
+		exit(1);
+	}
+}
+
+fn run(args: &Args) -> Result<()> {
+	let pal = Arc::new(load_palette(&args.textures)?);
+	let keys = match &args.keys {
+		Some(p) => load_json(p)?,
+		None => default_keys(pal.len()),
+	};
+	let lights: Lights = match &args.lights {
+		Some(p) => load_json(p)?,
+		None => Lights::new(),
+	};
+	lights.validate()?;
+
+	let mut runs = vec![bench(&pal, &lights, &keys, args.marcher, args)];
+	if args.compare {
+		runs.push(bench(&pal, &lights, &keys, Marcher::Scalar, args));
+		println!("speed-up: {:.2}x", runs[1].secs / runs[0].secs);
+	}
+
+	if let Some(p) = &args.json {
+		let f = File::create(p)?;
+		let mut b = BufWriter::new(f);
+		serde_json::to_writer_pretty(
+			&mut b,
+			&Report {
+				lights: &lights,
+				runs,
+			},
+		)?;
+		b.flush()?;
+		println!("wrote {}", p.to_string_lossy());
+	}
+	Ok(())
+}
+
+// Bake keys (args.repeat times) on args.threads threads.
+fn bench(
+	pal: &Arc<Vec<Surface>>,
+	lights: &Lights,
+	keys: &[TileKey],
+	m: Marcher,
+	args: &Args,
+) -> Run {
+	let mut data = SharedData::new(pal.clone(), lights.clone());
+	data.set_marcher(m);
+	let data = Arc::new(data);
+	let keys = Arc::new(keys.to_vec());
+	let tiles = keys.len() * args.repeat;
+	let next = Arc::new(AtomicUsize::new(0));
+
+	let start = Instant::now();
+	let workers: Vec<_> = (0..args.threads)
+		.map(|_| {
+			let (data, keys, next) = (data.clone(), keys.clone(), next.clone());
+			thread::spawn(move || {
+				let start = thread_cpu_secs();
+				loop {
+					let i = next.fetch_add(1, SeqCst);
+					if i >= tiles {
+						return thread_cpu_secs() - start;
+					}
+					data.render_central_block(keys[i % keys.len()]);
+				}
+			})
+		})
+		.collect();
+	let thread_cpu_secs: Vec<f64> = workers
+		.into_iter()
+		.map(|w| w.join().expect("bench worker panicked"))
+		.collect();
+	let secs = start.elapsed().as_secs_f64();
+
+	let rays = tiles * lights.rays_per_tile();
+	let run = Run {
+		marcher: m,
+		threads: args.threads,
+		tiles,
+		rays,
+		secs,
+		tiles_per_sec: tiles as f64 / secs,
+		rays_per_sec: rays as f64 / secs,
+		thread_cpu_secs,
+	};
+	println!(
+		"{:?}: {} tiles in {:.3} s on {} threads: {:.1} tiles/s, {:.3e} rays/s",
+		run.marcher, run.tiles, run.secs, run.threads, run.tiles_per_sec, run.rays_per_sec
+	);
+	for (i, cpu) in run.thread_cpu_secs.iter().enumerate() {
+		println!("  thread {}: {:.3} s CPU", i, cpu);
+	}
+	run
+}
+
+// CPU time used by the calling thread so far.
+// Unlike wall-clock time, this excludes time the thread was preempted,
+// so it shows how evenly work is spread even when threads outnumber cores.
+fn thread_cpu_secs() -> f64 {
+	let mut t = libc::timespec {
+		tv_sec: 0,
+		tv_nsec: 0,
+	};
+	// Safe: t is a valid timespec to write to, and the clock id is valid on all supported platforms.
+	let ret = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut t) };
+	assert_eq!(ret, 0, "clock_gettime(CLOCK_THREAD_CPUTIME_ID)");
+	t.tv_sec as f64 + t.tv_nsec as f64 * 1e-9
+}
+
+// Every block in the palette, once on its own and once with higher neighbors that cast shadows.
+fn default_keys(n: usize) -> Vec<TileKey> {
+	let mut keys = Vec::new();
+	for center in 1..n {
+		keys.push(TileKey::with_center(center as u8));
+		let mut k = TileKey::with_center(center as u8);
+		k.set(-1, -1, ((center % (n - 1)) + 1) as u8);
+		k.set(0, -1, ((center * 7 % (n - 1)) + 1) as u8);
+		keys.push(k);
+	}
+	keys
+}
+
+fn load_json<T: serde::de::DeserializeOwned>(p: &Path) -> Result<T> {
+	check_exists(p)?;
+	let f = File::open(p)?;
+	Ok(serde_json::from_reader(BufReader::new(f))?)
+}
+
+// Parse command-line arguments (without the program name), None means --help.
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Args>> {
+	let mut a = Args {
+		keys: None,
+		textures: PathBuf::from(DEFAULT_TEXTURE_DIR),
+		lights: None,
+		threads: num_cpus::get(),
+		repeat: 1,
+		marcher: Marcher::Packet,
+		compare: false,
+		json: None,
+	};
+	let count = |s: String| match s.parse() {
+		Ok(n) if n > 0 => Ok(n),
+		_ => GenError::new(format!("invalid number: {}", s)),
+	};
+
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		let mut value = || match args.next() {
+			Some(v) => Ok(v),
+			None => GenError::new(format!("{} needs a value", arg)),
+		};
+		match arg.as_str() {
+			"--keys" => a.keys = Some(PathBuf::from(value()?)),
+			"--lights" => a.lights = Some(PathBuf::from(value()?)),
+			"--textures" => a.textures = PathBuf::from(value()?),
+			"--threads" => a.threads = count(value()?)?,
+			"--repeat" => a.repeat = count(value()?)?,
+			"--marcher" => a.marcher = value()?.parse()?,
+			"--compare" => a.compare = true,
+			"--json" => a.json = Some(PathBuf::from(value()?)),
+			"-h" | "--help" => return Ok(None),
+			_ => return GenError::new(format!("unexpected argument: {}", arg)),
+		}
+	}
+	if a.compare && a.marcher == Marcher::Scalar {
+		return GenError::new(
+			"--compare bakes with the scalar marcher already, not with --marcher scalar".into(),
+		);
+	}
+	Ok(Some(a))
+}
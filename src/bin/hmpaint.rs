@@ -1,58 +1,122 @@
 use flux::prelude::*;
-use std::path::PathBuf;
+use std::env;
+use std::process::exit;
 
-const W: i32 = flux::game::gamestate::GRID as i32;
+const USAGE: &str = "usage: hmpaint [options] [recipe...]
 
-type V = Vector2<f64>;
+Builds procedural textures from recipes (see src/tracer/procedural.rs):
+<recipe>.hm.png, and <recipe>.dm.png if the recipe has a diffuse map.
+Builds all recipes if none are given.
 
-fn main() {
-	paint("one", |_| 1.0);
-
-	paint("sphere", |uv| {
-		let xy = (uv - Vector2(0.5, 0.5)) * 4.0;
-		sqrt(1.0 - xy.dot(xy))
-	});
+options:
+  --recipes <json>                 recipe file (default: <textures>/recipes.json)
+  --textures <dir>                 output directory (default assets/textures)
+  --size <n>                       pixels per side (default: one block)
+  --list                           list the recipes, do not build
+";
 
-	paint("cylinder", |uv| {
-		let Vector2(x, _y) = (uv - Vector2(0.5, 0.5)) * 4.0;
-		max(sqrt(1.0 - sqr(x)), sqrt(1.0 - sqr(x)))
-	});
+struct Args {
+	textures: PathBuf,
+	recipes: Option<PathBuf>,
+	size: i32,
+	list: bool,
+	names: Vec<String>,
 }
 
-fn paint<F: Fn(V) -> f64>(name: &str, f: F) {
-	let img = Image::<u8>::from_fn((W, W), |x, y| {
-		let u = (x as f64 + 0.5) / (W as f64);
-		let v = (y as f64 + 0.5) / (W as f64);
-		let c = (clip(f(Vector2(u, v))) * 255.0) as u8;
-		//println!("{},{}: {} => {}", x, y, f(u, v), c);
-		c
-	});
-	let path = PathBuf::from("assets/textures").join(PathBuf::from(name).with_extension("hm.png"));
-	img.save(&path).expect("saving image");
-	println!("wrote {}", &path.to_string_lossy());
-}
-
-fn sqrt(x: f64) -> f64 {
-	if x < 0.0 {
-		return 0.0;
+fn main() {
+	let args = match parse_args(env::args().skip(1)) {
+		Ok(Some(args)) => args,
+		Ok(None) => {
+			print!("{}", USAGE);
+			return;
+		}
+		Err(e) => {
+			eprintln!("{}\n\n{}", e, USAGE);
+			exit(2);
+		}
+	};
+	if let Err(e) = run(&args) {
+		eprintln!("error: {}", e);
+		exit(1);
 	}
-	f64::sqrt(x)
 }
 
-fn sqr(x: f64) -> f64 {
-	x * x
-}
+fn run(args: &Args) -> Result<()> {
+	let dir = &args.textures;
+	let file = args
+		.recipes
+		.clone()
+		.unwrap_or_else(|| dir.join("recipes.json"));
+	let recipes = load_recipes(&file)?;
 
-fn clip(x: f64) -> f64 {
-	if x > 1.0 {
-		return 1.0;
+	if args.list {
+		for (name, r) in &recipes {
+			let dm = if r.diffuse.is_some() {
+				" (with diffuse map)"
+			} else {
+				""
+			};
+			println!("{}{}", name, dm);
+		}
+		return Ok(());
 	}
-	if x < 0.0 {
-		return 0.0;
+
+	let names: Vec<&String> = match args.names.len() {
+		0 => recipes.keys().collect(),
+		_ => args.names.iter().collect(),
+	};
+	for name in names {
+		let r = match recipes.get(name) {
+			Some(r) => r,
+			None => {
+				return GenError::new(format!("no recipe {} in {}", name, file.to_string_lossy()))
+			}
+		};
+		let base = dir.join(name);
+		let hm = base.with_extension("hm.png");
+		r.height_map(args.size).save(&hm)?;
+		println!("wrote {}", hm.to_string_lossy());
+		if let Some(img) = r.diffuse_map(args.size) {
+			let dm = base.with_extension("dm.png");
+			img.save(&dm)?;
+			println!("wrote {}", dm.to_string_lossy());
+		}
 	}
-	if x != x {
-		// NaN
-		return 0.0;
+	Ok(())
+}
+
+// Parse command-line arguments (without the program name), None means --help.
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Args>> {
+	let mut a = Args {
+		textures: PathBuf::from(DEFAULT_TEXTURE_DIR),
+		recipes: None,
+		size: GRID as i32,
+		list: false,
+		names: Vec::new(),
+	};
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		let mut value = || match args.next() {
+			Some(v) => Ok(v),
+			None => GenError::new(format!("{} needs a value", arg)),
+		};
+		match arg.as_str() {
+			"--recipes" => a.recipes = Some(PathBuf::from(value()?)),
+			"--textures" => a.textures = PathBuf::from(value()?),
+			"--size" => {
+				let v = value()?;
+				a.size = match v.parse() {
+					Ok(n) if n > 0 => n,
+					_ => return GenError::new(format!("invalid size: {}", v)),
+				}
+			}
+			"--list" => a.list = true,
+			"-h" | "--help" => return Ok(None),
+			_ if arg.starts_with('-') => {
+				return GenError::new(format!("unexpected argument: {}", arg))
+			}
+			_ => a.names.push(arg),
+		}
 	}
-	x
+	Ok(Some(a))
 }
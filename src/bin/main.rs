@@ -1,21 +1,79 @@
 extern crate rand;
 use flux::editor::prelude::*;
 use flux::game::sdl_interface;
+use flux::prelude::*;
 use std::env;
-use std::ffi::OsString;
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::process::exit;
 
 fn main() {
-	let args: Vec<OsString> = env::args_os().skip(1).collect();
+	let opts = match Options::parse(env::args().skip(1)) {
+		Ok(opts) => opts,
+		Err(e) => {
+			eprintln!("{}\n\n{}", e, USAGE);
+			exit(2);
+		}
+	};
+	let config = opts.config();
 
-	let mut ed = match args.len() {
-		0 => Editor::new(),
-		1 => Editor::load(PathBuf::from(&args[0])).expect("loading level"),
-		_ => {
-			write!(io::stderr(), "need 0 or 1 arguments, got {}", args.len()).unwrap();
-			std::process::exit(1);
+	let mut app: Box<dyn App> = match &opts.command {
+		Command::Play(None) => {
+			Box::new(Frontend::new(&levels_dir(), config).expect("listing levels"))
+		}
+		Command::Play(Some(level)) => Box::new(Frontend::play_level(level.clone(), config)),
+		Command::Edit(level) => {
+			Box::new(Editor::open(level.clone(), config).expect("loading level"))
+		}
+		Command::Bake { level, out, atlas } => exit(report(bake(level, out, atlas, &config))),
+		Command::Validate(level) => exit(report(validate(level))),
+		Command::Help => {
+			print!("{}", USAGE);
+			return;
 		}
 	};
-	sdl_interface::mainloop(&mut ed).expect("initialize SDL");
+	sdl_interface::mainloop(
+		app.as_mut(),
+		opts.tick_rate,
+		opts.window_size,
+		opts.fullscreen,
+	)
+	.expect("initialize SDL");
+}
+
+// exit status for the result of a command without window.
+fn report(result: Result<()>) -> i32 {
+	match result {
+		Ok(()) => 0,
+		Err(e) => {
+			eprintln!("error: {}", e);
+			1
+		}
+	}
+}
+
+fn bake(level: &Path, out: &Path, atlas: &Option<PathBuf>, config: &Config) -> Result<()> {
+	let data = LevelData::load(level)?;
+	let goodies = data.goodies_map();
+	let map = Map::from(data.map_bytes, goodies, data.lights, config)?;
+	map.render_level().save(out)?;
+	println!("wrote {}", out.to_string_lossy());
+	if let Some(atlas) = atlas {
+		map.render_atlas().save(atlas)?;
+		println!(
+			"wrote {} and {}",
+			atlas.to_string_lossy(),
+			index_file(atlas).to_string_lossy()
+		);
+	}
+	Ok(())
+}
+
+fn validate(level: &Path) -> Result<()> {
+	let problems = LevelData::load(level)?.validate();
+	for p in &problems {
+		println!("{}: {}", level.to_string_lossy(), p);
+	}
+	match problems.len() {
+		0 => Ok(()),
+		n => GenError::new(format!("{}: {} problem(s)", level.to_string_lossy(), n)),
+	}
 }
@@ -5,5 +5,7 @@ pub mod prelude;
 pub mod pt;
 pub mod rect;
 pub mod result;
+pub mod timestep;
 pub mod vector;
 pub mod vector2;
+pub mod watcher;
@@ -4,5 +4,7 @@ pub use super::matrix::*;
 pub use super::pt::*;
 pub use super::rect::*;
 pub use super::result::*;
+pub use super::timestep::*;
 pub use super::vector::*;
 pub use super::vector2::*;
+pub use super::watcher::*;
@@ -1,7 +1,8 @@
 use crate::prelude::*;
+use serde::{Deserialize, Serialize};
 
 /// A half-open rectangle
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Rect {
 	pub min: Pt, // top-left vertex, considered inside
 	pub max: Pt, // bottom-right vertex, considered outside
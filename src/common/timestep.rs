@@ -0,0 +1,104 @@
+use crate::prelude::*;
+use std::time::Duration;
+
+/// Fixed-timestep clock: converts real elapsed time into a whole number of game ticks,
+/// so that the game runs at the same speed regardless of the display's refresh rate.
+///
+/// Time that does not add up to a full tick is kept in an accumulator for the next frame.
+/// Its fraction of a tick (alpha) can be used to interpolate drawing between the last two ticks.
+pub struct Timestep {
+	tick: Duration,
+	accumulator: Duration,
+	max_ticks: u32,
+	dropped: u64,
+}
+
+/// Game ticks per second, unless configured otherwise (see --tick-rate).
+pub const DEFAULT_TICK_RATE: u32 = 60;
+
+/// Range of configurable tick rates.
+/// Below the minimum, durations of a few ticks (see Physics) would round to zero.
+pub const MIN_TICK_RATE: u32 = 30;
+pub const MAX_TICK_RATE: u32 = 1000;
+
+impl Timestep {
+	/// Clock ticking ticks_per_second, running at most max_ticks per frame.
+	/// When rendering is too slow to keep up, further ticks are dropped
+	/// (the game slows down rather than freezing while catching up).
+	pub fn new(ticks_per_second: u32, max_ticks: u32) -> Self {
+		assert!(ticks_per_second > 0);
+		Self {
+			tick: Duration::from_secs(1) / ticks_per_second,
+			accumulator: Duration::from_secs(0),
+			max_ticks,
+			dropped: 0,
+		}
+	}
+
+	/// Add real time elapsed since the previous frame,
+	/// return the number of ticks to run now.
+	pub fn advance(&mut self, elapsed: Duration) -> u32 {
+		self.accumulator += elapsed;
+		let mut n = 0;
+		while self.accumulator >= self.tick {
+			self.accumulator -= self.tick;
+			n += 1;
+		}
+		if n > self.max_ticks {
+			self.dropped += (n - self.max_ticks) as u64;
+			n = self.max_ticks;
+		}
+		n
+	}
+
+	/// Total number of ticks dropped so far because rendering could not keep up.
+	pub fn dropped_ticks(&self) -> u64 {
+		self.dropped
+	}
+
+	/// Fraction of a tick (0..1) elapsed since the latest tick.
+	/// Drawing at prev + alpha * (current - prev) gives smooth motion on displays
+	/// that refresh faster than the tick rate.
+	pub fn alpha(&self) -> f64 {
+		self.accumulator.as_secs_f64() / self.tick.as_secs_f64()
+	}
+}
+
+/// Position between a and b, at fraction alpha (0..1), rounded to whole pixels.
+pub fn interpolate(a: Pt, b: Pt, alpha: f64) -> Pt {
+	let lerp = |a: i32, b: i32| a + ((b - a) as f64 * alpha).round() as i32;
+	Pt(lerp(a.0, b.0), lerp(a.1, b.1))
+}
+
+#[test]
+fn test_timestep() {
+	let ms = Duration::from_millis;
+	let mut ts = Timestep::new(50, 3); // 20 ms ticks
+
+	assert_eq!(ts.advance(ms(10)), 0);
+	assert!((ts.alpha() - 0.5).abs() < 1e-9);
+	assert_eq!(ts.advance(ms(15)), 1);
+	assert!((ts.alpha() - 0.25).abs() < 1e-9);
+
+	// 60 Hz vs 144 Hz displays: same number of ticks per second.
+	for &fps in &[60, 144] {
+		let mut ts = Timestep::new(50, 3);
+		let ticks: u32 = (0..fps)
+			.map(|_| ts.advance(Duration::from_secs(1) / fps))
+			.sum();
+		assert!(ticks == 49 || ticks == 50, "{} Hz: {} ticks", fps, ticks);
+	}
+
+	// catch-up is capped, the rest is dropped.
+	let mut ts = Timestep::new(50, 3);
+	assert_eq!(ts.advance(ms(1000)), 3);
+	assert_eq!(ts.advance(ms(0)), 0);
+	assert_eq!(ts.dropped_ticks(), 47);
+}
+
+#[test]
+fn test_interpolate() {
+	assert_eq!(interpolate(Pt(0, 10), Pt(10, 0), 0.0), Pt(0, 10));
+	assert_eq!(interpolate(Pt(0, 10), Pt(10, 0), 0.5), Pt(5, 5));
+	assert_eq!(interpolate(Pt(0, 10), Pt(10, 0), 1.0), Pt(10, 0));
+}
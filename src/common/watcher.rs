@@ -0,0 +1,39 @@
+use crate::prelude::*;
+use std::fs;
+use std::time::SystemTime;
+
+/// Polls a file's modification time to detect changes.
+/// Used to hot-reload data files while the game is running.
+pub struct FileWatcher {
+	path: PathBuf,
+	modified: Option<SystemTime>,
+}
+
+impl FileWatcher {
+	/// Start watching a file. Only changes after this point are reported.
+	/// The file does not need to exist yet.
+	pub fn new(path: &Path) -> Self {
+		let path = path.to_owned();
+		let modified = Self::mtime(&path);
+		Self { path, modified }
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Has the file been modified (or created, or removed) since the last call?
+	pub fn changed(&mut self) -> bool {
+		let modified = Self::mtime(&self.path);
+		if modified != self.modified {
+			self.modified = modified;
+			true
+		} else {
+			false
+		}
+	}
+
+	fn mtime(path: &Path) -> Option<SystemTime> {
+		fs::metadata(path).and_then(|m| m.modified()).ok()
+	}
+}
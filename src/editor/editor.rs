@@ -14,26 +14,61 @@ pub struct Editor {
 	selection_start: Pt,
 	selection_end: Pt,
 
+	// level-specific changes to the physics profile, if any.
+	physics: Option<PhysicsOverride>,
+	// level option, see LevelData.
+	reset_goodies: bool,
+	// the physics profile, as last loaded without errors.
+	profile: Physics,
+	// physics profile and level file, polled for changes.
+	physics_watcher: FileWatcher,
+	level_watcher: FileWatcher,
+	// block surfaces (for the palette buttons), and their textures polled for changes.
+	surfaces: Arc<Vec<Surface>>,
+	textures: TextureWatcher,
+
 	// None in edit mode, Some game in play mode
 	game: Option<GameState>,
+
+	// warning shown in a banner for this many more ticks.
+	warning: String,
+	warning_ticks: i32,
+	config: Config,
+	font: Font,
+	tick_rate: u32,
 }
 
 impl Editor {
 	/// Editor with empty map.
-	pub fn new() -> Self {
-		Self::from_data(LevelData::new(), PathBuf::from("level.json"))
+	pub fn new(config: Config) -> Result<Self> {
+		Self::from_data(LevelData::new(), PathBuf::from("level.json"), config)
 	}
 
 	/// Editor with map loaded from a JSON file.
-	pub fn load(p: PathBuf) -> Result<Editor> {
+	pub fn load(p: PathBuf, config: Config) -> Result<Editor> {
 		let data = LevelData::load(&p)?;
-		Ok(Self::from_data(data, p))
+		Self::from_data(data, p, config)
 	}
 
-	fn from_data(data: LevelData, path: PathBuf) -> Self {
+	/// Like load, but starts with an empty map (saved to p) if the file does not exist yet.
+	pub fn open(p: PathBuf, config: Config) -> Result<Editor> {
+		if p.exists() {
+			Self::load(p, config)
+		} else {
+			Self::from_data(LevelData::new(), p, config)
+		}
+	}
+
+	fn from_data(data: LevelData, path: PathBuf, config: Config) -> Result<Self> {
 		let goodies = data.goodies_map();
-		let (map, lights) = (data.map_bytes, data.lights);
-		Self {
+		let (map, lights, physics) = (data.map_bytes, data.lights, data.physics);
+		let reset_goodies = data.reset_goodies;
+		let dir = &config.texture_dir;
+		let surfaces = Arc::new(load_palette(dir)?);
+		Ok(Self {
+			profile: Physics::load_default(),
+			physics_watcher: FileWatcher::new(&physics_file()),
+			level_watcher: FileWatcher::new(&path),
 			path,
 			view_origin: Pt(0, 0),
 			view_zoom: 1,
@@ -41,15 +76,32 @@ impl Editor {
 			selection_end: Pt(0, 0),
 			brush_bar: Toolbar::new(
 				Pt(5, 2),
-				Texture::load_many(&["pencil", "pencil_rect"]).unwrap(),
-			),
+				Texture::load_many(dir, &["pencil", "pencil_rect"])?,
+				dir,
+			)?,
 			palette_bar: Toolbar::new(
 				Pt(5, GRID as i32 + 2 + 5),
-				Self::init_palette(default_palette(), &ED_PALETTE),
+				Self::init_palette(&surfaces, &ED_PALETTE),
+				dir,
+			)?,
+			map: Map::with_palette(
+				map,
+				goodies,
+				lights,
+				surfaces.as_ref().clone(),
+				config.render,
 			),
-			map: Map::from(map, goodies, lights),
+			textures: TextureWatcher::new(dir),
+			surfaces,
+			physics,
+			reset_goodies,
 			game: None,
-		}
+			warning: String::new(),
+			warning_ticks: 0,
+			font: Font::new(BGRA(255, 255, 255, 255)),
+			tick_rate: DEFAULT_TICK_RATE,
+			config,
+		})
 	}
 
 	fn save(&self) -> Result<()> {
@@ -58,15 +110,37 @@ impl Editor {
 			&self.map.bytemap(),
 			&self.map.goodies(),
 			&self.map.lights(),
+			&self.physics,
+			self.reset_goodies,
 		)
 	}
 
+	/// Physics for playing this level: the default profile,
+	/// with the level's changes if valid.
+	fn physics(&self) -> Physics {
+		match &self.physics {
+			None => self.profile.clone(),
+			Some(o) => o.apply(&self.profile).unwrap_or_else(|e| {
+				eprintln!("level physics: {}, using the profile", e);
+				self.profile.clone()
+			}),
+		}
+	}
+
 	pub const BG: BGRA = BGRA(255, 255, 255, 255);
 
 	// ray trace surface palette into textures,
 	// with very simple lighting.
 	// textures used as toolbar buttons and to draw the map.
-	fn init_palette(palette: Vec<Surface>, block_defs: &[BlockDef]) -> Vec<Texture> {
+	fn init_palette(surfaces: &Arc<Vec<Surface>>, block_defs: &[BlockDef]) -> Vec<Texture> {
+		block_defs
+			.iter()
+			.map(|def| Self::palette_button(surfaces, def.uid))
+			.collect()
+	}
+
+	// ray trace one block for its toolbar button.
+	fn palette_button(surfaces: &Arc<Vec<Surface>>, uid: u8) -> Texture {
 		let boring_lights = Lights {
 			sun_dir: Vec3(1.0, -1.0, 3.0).normalized(),
 			sun_intens: RGBf(0.5, 0.5, 0.6),
@@ -76,29 +150,27 @@ impl Editor {
 			ambient_rays: 0,
 			fake_ambient: RGBf(0.5, 0.5, 0.6),
 			invert_dm: false,
+			seed: 0,
+			denoise: Denoise::Off,
+			neighborhood: 1,
 		};
-
-		let n = palette.len();
-		let renderer = SharedData::new(Arc::new(palette), boring_lights);
-		let mut texs = Vec::<Texture>::with_capacity(n);
-		for i in 0..n {
-			let uid = block_defs[i].uid;
-			//println!("editor: rendering block {}", uid);
-			let tex = Texture::new(renderer.render_central_block(TileKey::with_center(uid)));
-			texs.push(tex);
-		}
-
-		texs
+		let renderer = SharedData::new(surfaces.clone(), boring_lights);
+		Texture::new(renderer.render_central_block(TileKey::with_center(uid)))
 	}
 
 	// ------------------------------------------------------------------------------ draw
 
-	pub fn draw(&self, disp: &mut SDLDisplay) {
+	/// Draw the game at a fraction alpha (0..1) of the way between the previous and current tick.
+	pub fn draw(&self, disp: &mut SDLDisplay, alpha: f64) {
 		if !self.is_paused() {
-			self.game.as_ref().unwrap().draw(disp);
-			return;
+			self.game.as_ref().unwrap().draw(disp, alpha);
+		} else {
+			self.draw_editor(disp);
 		}
+		self.draw_warning(disp);
+	}
 
+	fn draw_editor(&self, disp: &mut SDLDisplay) {
 		let mut view = Viewport::with_zoom(disp, self.view_origin, self.view_zoom);
 		view.clear(Self::BG);
 
@@ -118,6 +190,40 @@ impl Editor {
 		self.palette_bar.draw(disp);
 	}
 
+	// banner with the warning text at the top of the screen, while there is a warning.
+	fn draw_warning(&self, disp: &mut SDLDisplay) {
+		if self.warning_ticks > 0 {
+			let (w, _) = disp.dimensions();
+			let (_, th) = Font::text_dimensions(&self.warning, 2);
+			disp.fill_rect(Self::WARNING_COLOR, Pt(0, 0), (w, th + 16));
+			disp.draw_text(&self.font, &self.warning, Pt(8, 8), 2);
+		}
+	}
+
+	const WARNING_COLOR: BGRA = BGRA(0, 0, 255, 200);
+
+	/// Report a problem the player should know about (e.g. conflicting key bindings),
+	/// on the console and with a warning banner on screen.
+	/// Warnings in quick succession are shown together, one per line.
+	pub fn warn(&mut self, msg: &str) {
+		eprintln!("warning: {}", msg);
+		if self.warning_ticks == 0 {
+			self.warning.clear();
+		}
+		if !self.warning.is_empty() {
+			self.warning.push('\n');
+		}
+		self.warning.push_str(msg);
+		self.warning_ticks = Self::WARNING_TICKS;
+	}
+
+	const WARNING_TICKS: i32 = 300;
+
+	/// Ticks per second the main loop runs at.
+	pub fn set_tick_rate(&mut self, tick_rate: u32) {
+		self.tick_rate = tick_rate;
+	}
+
 	fn draw_selection(&self, view: &mut Viewport) {
 		let grid = GRID as i32;
 		let min = self.selection().min;
@@ -133,11 +239,83 @@ impl Editor {
 	// ------------------------------------------------------------------------------- tick
 
 	pub fn tick(&mut self) {
+		self.warning_ticks = max(0, self.warning_ticks - 1);
+		self.hot_reload();
+		self.hot_reload_textures();
 		if !self.is_paused() {
 			self.game.as_mut().unwrap().tick();
 		}
 	}
 
+	// Pick up changes to the physics profile, or the level's physics or lights,
+	// so that they can be tweaked without restarting.
+	// Blocks are not reloaded: the editor's map is the one being edited.
+	// A broken file (e.g. a typo while editing it) is reported, and the last good values kept.
+	fn hot_reload(&mut self) {
+		let physics_changed = self.physics_watcher.changed();
+		let level_changed = self.level_watcher.changed();
+		if physics_changed {
+			match Physics::load(&physics_file()) {
+				Ok(profile) => {
+					self.profile = profile;
+					println!("reloaded physics");
+				}
+				Err(e) => self.warn(&format!(
+					"reloading {}: {}, keeping the last physics",
+					physics_file().to_string_lossy(),
+					e
+				)),
+			}
+		}
+		if level_changed {
+			match LevelData::load(&self.path) {
+				Ok(data) => {
+					self.physics = data.physics;
+					self.reset_goodies = data.reset_goodies;
+					// the game shares the map's renderer, so its tiles pick up the lights too,
+					// but the hamster's sprites are lit separately.
+					if data.lights != self.map.lights() {
+						if let Some(game) = self.game.as_mut() {
+							game.set_lights(&data.lights);
+						}
+						self.map.set_lights(data.lights);
+						println!("reloaded lights");
+					}
+				}
+				Err(e) => eprintln!("reloading {}: {}", self.path.to_string_lossy(), e),
+			}
+		}
+		if (physics_changed || level_changed) && !self.is_paused() {
+			let physics = self.physics();
+			let reset_goodies = self.reset_goodies;
+			let game = self.game.as_mut().unwrap();
+			game.set_physics(physics);
+			game.set_reset_goodies(reset_goodies);
+		}
+	}
+
+	// Pick up textures changed on disk (e.g. by an artist):
+	// re-bake only the tiles that show them, and update their palette buttons.
+	fn hot_reload_textures(&mut self) {
+		for uid in self.textures.changed() {
+			// may fail while the file is being written, it will change again when done.
+			let surface = match load_surface(&self.config.texture_dir, uid) {
+				Ok(surface) => surface,
+				Err(e) => {
+					eprintln!("reloading textures of block {}: {}", uid, e);
+					continue;
+				}
+			};
+			Arc::make_mut(&mut self.surfaces)[uid as usize] = surface.clone();
+			self.map.set_surface(uid, surface);
+			if let Some(i) = ED_PALETTE.iter().position(|def| def.uid == uid) {
+				let button = Self::palette_button(&self.surfaces, uid);
+				self.palette_bar.set_button(i, button);
+			}
+			println!("reloaded textures of block {}", uid);
+		}
+	}
+
 	// ------------------------------------------------------------------------------- events
 
 	pub fn mouse_button(&mut self, pos: Pt, left: bool, right: bool, down: bool) {
@@ -191,6 +369,7 @@ impl Editor {
 			if right {
 				if self.map.goodie_at(pos) != 0 {
 					self.map.set_goodie(pos, 0);
+					self.map.texture_at_urgent(pos);
 				} else {
 					self.set(pos, 0);
 				}
@@ -250,8 +429,10 @@ impl Editor {
 		}
 	}
 
+	// Set a block, and bake its new tile first: that is where the user is looking.
 	fn set(&mut self, pos: Pt, blk: u8) {
 		self.map.set(pos, blk);
+		self.map.texture_at_urgent(pos);
 	}
 
 	fn clear_selection(&mut self) {
@@ -344,9 +525,17 @@ impl Editor {
 			self.save().expect("saving level");
 		}
 		self.game = match self.game {
-			None => Some(GameState::new(self.map.clone())), // TODO: translate map
+			None => {
+				let dir = &self.config.texture_dir;
+				let mut game = GameState::new(self.map.clone(), self.physics(), dir); // TODO: translate map
+				game.set_tick_rate(self.tick_rate);
+				game.set_reset_goodies(self.reset_goodies);
+				Some(game)
+			}
 			Some(_) => None,
-		}
+		};
+		// the level was just saved: not a change to reload.
+		self.level_watcher = FileWatcher::new(&self.path);
 	}
 
 	/// Is the editor in "paused" (i.e. "editing") mode?
@@ -370,3 +559,41 @@ impl Editor {
 	//	println!("view_center: {}", self.view_center);
 	//}
 }
+
+impl App for Editor {
+	fn tick(&mut self) {
+		Editor::tick(self)
+	}
+
+	fn draw(&self, disp: &mut SDLDisplay, alpha: f64) {
+		Editor::draw(self, disp, alpha)
+	}
+
+	fn key_down(&mut self, k: Key) {
+		Editor::key_down(self, k)
+	}
+
+	fn key_up(&mut self, k: Key) {
+		Editor::key_up(self, k)
+	}
+
+	fn mouse_button(&mut self, pos: Pt, left: bool, right: bool, down: bool) {
+		Editor::mouse_button(self, pos, left, right, down)
+	}
+
+	fn mouse_motion(&mut self, pos: Pt, left: bool, right: bool) {
+		Editor::mouse_motion(self, pos, left, right)
+	}
+
+	fn mouse_wheel(&mut self, x: i32, y: i32) {
+		Editor::mouse_wheel(self, x, y)
+	}
+
+	fn warn(&mut self, msg: &str) {
+		Editor::warn(self, msg)
+	}
+
+	fn set_tick_rate(&mut self, tick_rate: u32) {
+		Editor::set_tick_rate(self, tick_rate)
+	}
+}
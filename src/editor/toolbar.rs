@@ -11,14 +11,14 @@ pub struct Toolbar {
 }
 
 impl Toolbar {
-	pub fn new(screen_pos: Pt, palette: Vec<Texture>) -> Self {
-		Self {
+	pub fn new(screen_pos: Pt, palette: Vec<Texture>, texture_dir: &Path) -> Result<Self> {
+		Ok(Self {
 			screen_pos,
 			columns: 4,
 			buttons: palette,
-			tex_selected: Texture::load("selected").unwrap(), // TODO: could be deduped
+			tex_selected: Texture::load(texture_dir, "selected")?, // TODO: could be deduped
 			selected: 0,
-		}
+		})
 	}
 
 	pub fn dimensions(&self) -> (i32, i32) {
@@ -45,6 +45,11 @@ impl Toolbar {
 		Rect::new(self.screen_pos, self.dimensions()).is_inside(pos)
 	}
 
+	/// Replace the texture of the i'th button (e.g. after its block changed).
+	pub fn set_button(&mut self, i: usize, tex: Texture) {
+		self.buttons[i] = tex;
+	}
+
 	pub fn selected(&self) -> usize {
 		self.selected
 	}
@@ -0,0 +1,401 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+
+/// JSON description of a sprite sheet, stored next to its PNG.
+/// E.g. "hamster.anim.json" describes "hamster.png":
+///
+/// ```json
+/// {
+///     "grid": [1, 8],
+///     "heightmap": "hamster.hm",
+///     "clips": {
+///         "idle": {"frames": [[0, 30], [1, 8]]},
+///         "land": {"frames": [[6, 4], [7, 4]], "looping": false}
+///     }
+/// }
+/// ```
+///
+/// The sheet is cut into a grid of equally sized frames, numbered left-to-right, top-to-bottom.
+/// Each clip lists (frame number, duration in ticks).
+/// The optional heightmap (e.g. "hamster.hm.png") is stretched to the size of the sheet,
+/// and used to light the sprite like the level's blocks (see SpriteSheet::lit).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SheetDesc {
+	/// Number of frame columns and rows in the sheet.
+	pub grid: (i32, i32),
+	#[serde(default)]
+	pub heightmap: Option<String>,
+	pub clips: FnvHashMap<String, ClipDesc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClipDesc {
+	pub frames: Vec<(usize, i32)>,
+	#[serde(default = "default_looping")]
+	pub looping: bool,
+}
+
+fn default_looping() -> bool {
+	true
+}
+
+impl SheetDesc {
+	/// A sheet consisting of a single, still frame.
+	pub fn still() -> Self {
+		Self {
+			grid: (1, 1),
+			heightmap: None,
+			clips: FnvHashMap::default(),
+		}
+	}
+
+	/// Load from JSON, e.g. "assets/textures/hamster.anim.json".
+	pub fn load(p: &Path) -> Result<Self> {
+		check_exists(p)?;
+		let f = File::open(p)?;
+		let b = BufReader::new(f);
+		Ok(serde_json::from_reader(b)?)
+	}
+}
+
+/// A sprite sheet cut into frames, with named animation clips (e.g. "idle", "walk").
+/// Shared between all sprites that look alike, see Animation for per-sprite playback state.
+pub struct SpriteSheet {
+	frames: Vec<Texture>,
+	// diffuse + height map of each frame, for lighting.
+	surfaces: Rc<Vec<Surface>>,
+	// drop shadow of each frame, empty if unlit.
+	shadows: Vec<Texture>,
+	shadow_offset: Pt,
+	clips: FnvHashMap<String, ClipDesc>,
+}
+
+impl SpriteSheet {
+	/// Clip an Animation starts with. Every sheet with clips must have it.
+	pub const IDLE_CLIP: &'static str = "idle";
+
+	/// Load "<basename>.png" and its description "<basename>.anim.json" from dir.
+	/// Without description, the whole PNG is a single still frame.
+	pub fn load(dir: &Path, basename: &str) -> Result<Self> {
+		let base = dir.join(basename);
+		let img = Image::<BGRA>::load(base.with_extension("png"))?;
+		let json = base.with_extension("anim.json");
+		let desc = if json.exists() {
+			SheetDesc::load(&json)?
+		} else {
+			SheetDesc::still()
+		};
+		match &desc.heightmap {
+			None => Self::new(img, desc),
+			Some(hm) => {
+				let hm = Image::<u8>::load(dir.join(hm).with_extension("png"))?;
+				Self::with_heightmap(img, &hm, desc)
+			}
+		}
+	}
+
+	/// Cut an image into frames according to desc. The sprite is flat (no height map).
+	pub fn new(img: Image<BGRA>, desc: SheetDesc) -> Result<Self> {
+		let hm = Image::new(img.dimensions());
+		Self::with_heightmap(img, &hm, desc)
+	}
+
+	/// Cut a diffuse map into frames according to desc,
+	/// with a height map that is stretched to the diffuse map's size.
+	pub fn with_heightmap(img: Image<BGRA>, hm: &Image<u8>, desc: SheetDesc) -> Result<Self> {
+		let (cols, rows) = desc.grid;
+		let (w, h) = img.dimensions();
+		if cols <= 0 || rows <= 0 || w % cols != 0 || h % rows != 0 {
+			return GenError::new(format!(
+				"sprite sheet of {}x{} pixels cannot be cut into {}x{} frames",
+				w, h, cols, rows
+			));
+		}
+		let dim = (w / cols, h / rows);
+
+		let (hw, hh) = hm.dimensions();
+		if hw == 0 || hh == 0 {
+			return GenError::new("sprite sheet: empty height map".into());
+		}
+		let hm = Image::from_fn((w, h), |x, y| hm.at((x * hw / w, y * hh / h)));
+
+		let mut frames = Vec::with_capacity((cols * rows) as usize);
+		let mut surfaces = Vec::with_capacity((cols * rows) as usize);
+		for iy in 0..rows {
+			for ix in 0..cols {
+				let pos = (ix * dim.0, iy * dim.1);
+				let dm = img.crop(pos, dim);
+				frames.push(Texture::new(dm.clone()));
+				surfaces.push(Surface::new(hm.crop(pos, dim), dm));
+			}
+		}
+
+		if !desc.clips.is_empty() && !desc.clips.contains_key(Self::IDLE_CLIP) {
+			return GenError::new(format!("sprite sheet: no {} clip", Self::IDLE_CLIP));
+		}
+		for (name, clip) in &desc.clips {
+			if clip.frames.is_empty() {
+				return GenError::new(format!("animation clip {}: no frames", name));
+			}
+			for &(i, ticks) in &clip.frames {
+				if i >= frames.len() || ticks <= 0 {
+					return GenError::new(format!(
+						"animation clip {}: bad frame ({}, {})",
+						name, i, ticks
+					));
+				}
+			}
+		}
+
+		Ok(Self {
+			frames,
+			surfaces: Rc::new(surfaces),
+			shadows: Vec::new(),
+			shadow_offset: Pt(0, 0),
+			clips: desc.clips,
+		})
+	}
+
+	/// Copy of this sheet with every frame ray traced under the given lights,
+	/// and with a drop shadow.
+	/// Each frame is shaded only once, not every time it is drawn.
+	pub fn lit(&self, lights: &Lights) -> Self {
+		Self {
+			frames: self
+				.surfaces
+				.iter()
+				.map(|s| Texture::new(shade_sprite(s, lights)))
+				.collect(),
+			surfaces: self.surfaces.clone(),
+			shadows: self
+				.surfaces
+				.iter()
+				.map(|s| Texture::new(drop_shadow(&s.dm)))
+				.collect(),
+			shadow_offset: drop_shadow_offset(lights, Self::SHADOW_DEPTH),
+			clips: self.clips.clone(),
+		}
+	}
+
+	/// Distance between sprites and the map behind them, in pixels.
+	/// Determines how far the drop shadow is offset.
+	const SHADOW_DEPTH: f64 = 8.0;
+
+	/// A sheet with just one frame, shown for every clip.
+	pub fn still(img: Image<BGRA>) -> Self {
+		Self::new(img, SheetDesc::still()).unwrap()
+	}
+
+	/// Size of a single frame, in pixels.
+	pub fn frame_dimensions(&self) -> (i32, i32) {
+		self.frames[0].dimensions()
+	}
+
+	/// The frame to show ticks after starting clip.
+	pub fn frame(&self, clip: &str, ticks: i32) -> &Texture {
+		&self.frames[self.frame_index(clip, ticks)]
+	}
+
+	/// The drop shadow to draw (offset by shadow_offset) under frame(clip, ticks).
+	/// None if the sheet is not lit.
+	pub fn shadow(&self, clip: &str, ticks: i32) -> Option<&Texture> {
+		self.shadows.get(self.frame_index(clip, ticks))
+	}
+
+	/// Position of the drop shadow relative to the sprite.
+	pub fn shadow_offset(&self) -> Pt {
+		self.shadow_offset
+	}
+
+	fn frame_index(&self, clip: &str, ticks: i32) -> usize {
+		let clip = match self.clip(clip) {
+			None => return 0,
+			Some(c) => c,
+		};
+
+		let total = Self::duration(clip);
+		let mut t = if clip.looping {
+			ticks.rem_euclid(total)
+		} else {
+			min(ticks, total - 1)
+		};
+		for &(i, dt) in &clip.frames {
+			if t < dt {
+				return i;
+			}
+			t -= dt;
+		}
+		unreachable!()
+	}
+
+	/// Has a non-looping clip played completely after ticks?
+	/// Looping clips are never done.
+	pub fn is_done(&self, clip: &str, ticks: i32) -> bool {
+		match self.clip(clip) {
+			None => true,
+			Some(c) => !c.looping && ticks >= Self::duration(c),
+		}
+	}
+
+	/// Check that the sheet has all clips that will be played (e.g. by a Hamster),
+	/// so that a missing one is reported when loading, not when played.
+	/// A still sheet (without clips) shows its only frame for any clip.
+	pub fn check_clips(&self, names: &[&str]) -> Result<()> {
+		if self.clips.is_empty() {
+			return Ok(());
+		}
+		let missing: Vec<&str> = names
+			.iter()
+			.cloned()
+			.filter(|n| !self.clips.contains_key(*n))
+			.collect();
+		match missing.len() {
+			0 => Ok(()),
+			_ => GenError::new(format!("sprite sheet: no clip {}", missing.join(", "))),
+		}
+	}
+
+	// None for a still sheet.
+	// Panics if the clip is missing: check_clips should have caught that.
+	fn clip(&self, name: &str) -> Option<&ClipDesc> {
+		if self.clips.is_empty() {
+			return None;
+		}
+		match self.clips.get(name) {
+			Some(c) => Some(c),
+			None => panic!("sprite sheet has no clip {}", name),
+		}
+	}
+
+	fn duration(clip: &ClipDesc) -> i32 {
+		clip.frames.iter().map(|f| f.1).sum()
+	}
+}
+
+/// Playback state of an animated sprite: which clip is playing since when.
+/// Usable by anything drawn from a SpriteSheet (hamster, enemies, goodies, ...).
+pub struct Animation {
+	sheet: Rc<SpriteSheet>,
+	clip: &'static str,
+	since: i32,
+}
+
+impl Animation {
+	pub fn new(sheet: Rc<SpriteSheet>) -> Self {
+		Self {
+			sheet,
+			clip: SpriteSheet::IDLE_CLIP,
+			since: 0,
+		}
+	}
+
+	pub fn sheet(&self) -> &SpriteSheet {
+		&self.sheet
+	}
+
+	/// Currently playing clip.
+	pub fn clip(&self) -> &'static str {
+		self.clip
+	}
+
+	/// Switch to a clip, starting from its first frame.
+	/// Does nothing if the clip is already playing, so that it can be called every tick.
+	pub fn play(&mut self, clip: &'static str, now: i32) {
+		if clip != self.clip {
+			self.clip = clip;
+			self.since = now;
+		}
+	}
+
+	/// Has the current clip played completely? (Never true for looping clips).
+	pub fn is_done(&self, now: i32) -> bool {
+		self.sheet.is_done(self.clip, now - self.since)
+	}
+
+	/// Frame to draw at time now.
+	pub fn frame(&self, now: i32) -> &Texture {
+		self.sheet.frame(self.clip, now - self.since)
+	}
+
+	/// Drop shadow to draw at time now, if the sheet is lit.
+	pub fn shadow(&self, now: i32) -> Option<&Texture> {
+		self.sheet.shadow(self.clip, now - self.since)
+	}
+
+	/// Replace the sheet, keeping the playback state. E.g. by a lit copy.
+	pub fn set_sheet(&mut self, sheet: Rc<SpriteSheet>) {
+		self.sheet = sheet;
+	}
+}
+
+#[test]
+fn test_sprite_sheet() {
+	let desc: SheetDesc = serde_json::from_str(
+		r#"{
+		"grid": [2, 2],
+		"clips": {
+			"idle": {"frames": [[0, 2], [1, 3]]},
+			"land": {"frames": [[2, 1], [3, 1]], "looping": false}
+		}
+	}"#,
+	)
+	.unwrap();
+	let sheet = SpriteSheet::new(Image::new((64, 32)), desc).unwrap();
+	assert_eq!(sheet.frame_dimensions(), (32, 16));
+
+	let uid = |clip, t| sheet.frame(clip, t).uid();
+	let frame = |i: usize| sheet.frames[i].uid();
+
+	// looping
+	assert_eq!(uid("idle", 0), frame(0));
+	assert_eq!(uid("idle", 1), frame(0));
+	assert_eq!(uid("idle", 2), frame(1));
+	assert_eq!(uid("idle", 4), frame(1));
+	assert_eq!(uid("idle", 5), frame(0));
+	assert!(!sheet.is_done("idle", 100));
+
+	// not looping: stay at the last frame
+	assert_eq!(uid("land", 1), frame(3));
+	assert_eq!(uid("land", 100), frame(3));
+	assert!(!sheet.is_done("land", 1));
+	assert!(sheet.is_done("land", 2));
+
+	// clips are checked up front
+	assert!(sheet.check_clips(&["idle", "land"]).is_ok());
+	let err = sheet.check_clips(&["idle", "walk", "jump"]).unwrap_err();
+	assert_eq!(err.to_string(), "sprite sheet: no clip walk, jump");
+	let still = SpriteSheet::still(Image::new((4, 4)));
+	assert!(still.check_clips(&["walk"]).is_ok());
+	assert_eq!(still.frame("walk", 3).uid(), still.frames[0].uid());
+
+	// unlit: no shadow
+	assert!(sheet.shadow("idle", 0).is_none());
+	let lit = sheet.lit(&Lights::new());
+	assert_eq!(lit.frame_dimensions(), (32, 16));
+	assert!(lit.shadow("idle", 0).is_some());
+	assert_ne!(lit.frame("idle", 0).uid(), sheet.frame("idle", 0).uid());
+}
+
+#[test]
+fn test_bad_sprite_sheet() {
+	let mut desc = SheetDesc::still();
+	desc.grid = (3, 1);
+	assert!(SpriteSheet::new(Image::new((64, 32)), desc.clone()).is_err());
+	desc.grid = (1, 1);
+	desc.clips.insert(
+		"idle".into(),
+		ClipDesc {
+			frames: vec![(1, 8)],
+			looping: true,
+		},
+	);
+	assert!(SpriteSheet::new(Image::new((64, 32)), desc.clone()).is_err());
+
+	// clips, but no idle clip
+	let walk = desc.clips.remove("idle").unwrap();
+	desc.clips.insert("walk".into(), walk);
+	desc.clips.get_mut("walk").unwrap().frames = vec![(0, 8)];
+	assert!(SpriteSheet::new(Image::new((64, 32)), desc).is_err());
+}
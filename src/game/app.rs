@@ -0,0 +1,29 @@
+use crate::prelude::*;
+
+/// An application driven by sdl_interface::mainloop: the game's front-end or the level editor.
+/// Receives logical keys (see Bindings), mouse events and fixed-rate ticks.
+pub trait App {
+	/// Advance the state by one tick.
+	fn tick(&mut self);
+
+	/// Draw at a fraction alpha (0..1) of the way between the previous and current tick.
+	fn draw(&self, disp: &mut SDLDisplay, alpha: f64);
+
+	fn key_down(&mut self, k: Key);
+	fn key_up(&mut self, k: Key);
+
+	fn mouse_button(&mut self, _pos: Pt, _left: bool, _right: bool, _down: bool) {}
+	fn mouse_motion(&mut self, _pos: Pt, _left: bool, _right: bool) {}
+	fn mouse_wheel(&mut self, _x: i32, _y: i32) {}
+
+	/// Report a problem the player should know about (e.g. conflicting key bindings).
+	fn warn(&mut self, msg: &str);
+
+	/// Ticks per second the main loop runs at.
+	fn set_tick_rate(&mut self, _tick_rate: u32) {}
+
+	/// Should the main loop exit? (E.g. "quit" was selected in a menu).
+	fn quit_requested(&self) -> bool {
+		false
+	}
+}
@@ -0,0 +1,306 @@
+use crate::prelude::*;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+
+/// A physical input, named like SDL names it
+/// (so that the translation to logical Keys can be tested without SDL).
+///
+/// Written as a string in the bindings file:
+///
+/// ```text
+/// "key:Left Alt"  keyboard key, see SDL_GetKeyName
+/// "button:a"      game controller button, see SDL_GameControllerGetStringForButton
+/// "axis:leftx-"   game controller axis pushed to the negative (left/up) side,
+/// "axis:leftx+"   or to the positive side, see SDL_GameControllerGetStringForAxis
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Input {
+	Key(String),
+	Button(String),
+	Axis(String, bool),
+}
+
+impl Input {
+	pub fn parse(s: &str) -> Result<Self> {
+		let (kind, name) = match s.find(':') {
+			None => {
+				return GenError::new(format!(
+					"input \"{}\": need key:, button: or axis: prefix",
+					s
+				))
+			}
+			Some(i) => (&s[..i], &s[i + 1..]),
+		};
+		match kind {
+			"key" => Ok(Input::Key(name.into())),
+			"button" => Ok(Input::Button(name.into())),
+			"axis" if name.ends_with('-') => Ok(Input::Axis(name[..name.len() - 1].into(), false)),
+			"axis" if name.ends_with('+') => Ok(Input::Axis(name[..name.len() - 1].into(), true)),
+			"axis" => GenError::new(format!("input \"{}\": axis needs - or + suffix", s)),
+			_ => GenError::new(format!("input \"{}\": unknown kind \"{}\"", s, kind)),
+		}
+	}
+}
+
+impl fmt::Display for Input {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Input::Key(k) => write!(f, "key:{}", k),
+			Input::Button(b) => write!(f, "button:{}", b),
+			Input::Axis(a, true) => write!(f, "axis:{}+", a),
+			Input::Axis(a, false) => write!(f, "axis:{}-", a),
+		}
+	}
+}
+
+/// Bindings map physical inputs (keyboard, game controller) to logical Keys.
+/// Loaded from bindings_file(), which lists the inputs for each logical Key:
+///
+/// ```json
+/// {
+///     "Left": ["key:Left", "key:S", "button:dpleft", "axis:leftx-"],
+///     "A":    ["key:Space", "button:a"]
+/// }
+/// ```
+pub struct Bindings {
+	inputs: FnvHashMap<Input, Key>,
+	conflicts: Vec<String>,
+	// which axis directions are currently pushed, to send only changes.
+	axes_down: FnvHashSet<Input>,
+}
+
+/// Game controller axes must be pushed this far (out of 32767) to count as a key press.
+pub const AXIS_DEADZONE: i16 = 8000;
+
+impl Default for Bindings {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Bindings {
+	/// The built-in bindings.
+	pub fn new() -> Self {
+		let table = DEFAULT_BINDINGS
+			.iter()
+			.map(|(k, inputs)| (*k, inputs.iter().map(|s| s.to_string()).collect()))
+			.collect();
+		Self::from_table(table).unwrap()
+	}
+
+	/// Bindings from (logical key, inputs) pairs, as found in a bindings file.
+	/// An input bound to more than one key is a conflict,
+	/// it stays bound to the first key and is reported by conflicts().
+	pub fn from_table(mut table: Vec<(Key, Vec<String>)>) -> Result<Self> {
+		// file order is lost in the JSON map, sort to report conflicts deterministically.
+		table.sort_by_key(|(k, _)| *k as u8);
+
+		let mut inputs = FnvHashMap::default();
+		let mut conflicts = Vec::new();
+		for (key, names) in table {
+			for name in names {
+				let input = Input::parse(&name)?;
+				match inputs.get(&input) {
+					None => {
+						inputs.insert(input, key);
+					}
+					Some(&other) if other == key => (),
+					Some(&other) => conflicts.push(format!(
+						"{} is bound to both {:?} and {:?}",
+						input, other, key
+					)),
+				}
+			}
+		}
+		Ok(Self {
+			inputs,
+			conflicts,
+			axes_down: FnvHashSet::default(),
+		})
+	}
+
+	/// Load from JSON, e.g. "assets/bindings.json".
+	pub fn load(p: &Path) -> Result<Self> {
+		check_exists(p)?;
+		let f = File::open(p)?;
+		let b = BufReader::new(f);
+		let table: FnvHashMap<Key, Vec<String>> = serde_json::from_reader(b)?;
+		Self::from_table(table.into_iter().collect())
+	}
+
+	/// Load from bindings_file(), falling back to the built-in bindings
+	/// (with a warning) if the file is missing or broken.
+	pub fn load_default() -> Self {
+		match Self::load(&bindings_file()) {
+			Ok(b) => b,
+			Err(e) => {
+				eprintln!(
+					"loading {}: {}, using default bindings",
+					bindings_file().to_string_lossy(),
+					e
+				);
+				Self::new()
+			}
+		}
+	}
+
+	/// Inputs bound to more than one Key, as human-readable messages.
+	pub fn conflicts(&self) -> &[String] {
+		&self.conflicts
+	}
+
+	/// Logical key for a keyboard key name (e.g. "Left Alt"). Key::None if unbound.
+	pub fn key(&self, name: &str) -> Key {
+		self.lookup(&Input::Key(name.into()))
+	}
+
+	/// Logical key for a game controller button name (e.g. "dpleft"). Key::None if unbound.
+	pub fn button(&self, name: &str) -> Key {
+		self.lookup(&Input::Button(name.into()))
+	}
+
+	/// Key presses and releases (true = down) caused by moving
+	/// a game controller axis (e.g. "leftx") to a new value.
+	pub fn axis(&mut self, name: &str, value: i16) -> Vec<(Key, bool)> {
+		let mut events = Vec::new();
+		for &positive in &[false, true] {
+			let input = Input::Axis(name.into(), positive);
+			let key = self.lookup(&input);
+			if key == Key::None {
+				continue;
+			}
+			let pushed = if positive {
+				value > AXIS_DEADZONE
+			} else {
+				value < -AXIS_DEADZONE
+			};
+			let was_pushed = self.axes_down.contains(&input);
+			if pushed && !was_pushed {
+				self.axes_down.insert(input);
+				events.push((key, true));
+			} else if !pushed && was_pushed {
+				self.axes_down.remove(&input);
+				events.push((key, false));
+			}
+		}
+		events
+	}
+
+	fn lookup(&self, input: &Input) -> Key {
+		*self.inputs.get(input).unwrap_or(&Key::None)
+	}
+}
+
+/// The bindings used unless overridden by the user.
+pub fn bindings_file() -> PathBuf {
+	PathBuf::from("assets/bindings.json")
+}
+
+const DEFAULT_BINDINGS: &[(Key, &[&str])] = &[
+	(
+		Key::Left,
+		&["key:Left", "key:S", "key:J", "button:dpleft", "axis:leftx-"],
+	),
+	(
+		Key::Right,
+		&[
+			"key:Right",
+			"key:F",
+			"key:L",
+			"button:dpright",
+			"axis:leftx+",
+		],
+	),
+	(
+		Key::Up,
+		&["key:Up", "key:E", "key:I", "button:dpup", "axis:lefty-"],
+	),
+	(
+		Key::Down,
+		&["key:Down", "key:D", "key:K", "button:dpdown", "axis:lefty+"],
+	),
+	(Key::A, &["key:Space", "button:a"]),
+	(
+		Key::B,
+		&[
+			"key:Left Alt",
+			"key:Right Alt",
+			"button:x",
+			"axis:triggerright+",
+		],
+	),
+	(Key::ZoomIn, &["key:="]),
+	(Key::ZoomOut, &["key:-"]),
+	(Key::Pause, &["key:P", "button:start"]),
+	(Key::Save, &["key:W"]),
+	(Key::NextMap, &["key:N"]),
+	(Key::PrevMap, &["key:M"]),
+	(Key::Restart, &["key:R", "button:back"]),
+	(Key::Debug, &["key:F3"]),
+];
+
+#[test]
+fn test_parse_input() {
+	assert_eq!(
+		Input::parse("key:Left Alt").unwrap(),
+		Input::Key("Left Alt".into())
+	);
+	assert_eq!(Input::parse("button:a").unwrap(), Input::Button("a".into()));
+	assert_eq!(
+		Input::parse("axis:leftx-").unwrap(),
+		Input::Axis("leftx".into(), false)
+	);
+	assert!(Input::parse("leftx").is_err());
+	assert!(Input::parse("axis:leftx").is_err());
+	assert!(Input::parse("mouse:left").is_err());
+	for s in &["key:Space", "axis:lefty+", "axis:lefty-"] {
+		assert_eq!(&Input::parse(s).unwrap().to_string(), s);
+	}
+}
+
+#[test]
+fn test_default_bindings() {
+	let b = Bindings::new();
+	assert!(b.conflicts().is_empty());
+	assert_eq!(b.key("Left"), Key::Left);
+	assert_eq!(b.key("Right Alt"), Key::B);
+	assert_eq!(b.key("Q"), Key::None);
+	assert_eq!(b.button("a"), Key::A);
+	assert_eq!(b.button("dpleft"), Key::Left);
+}
+
+#[test]
+fn test_load_bindings() {
+	// the shipped file should be the defaults.
+	let file = Bindings::load(&bindings_file()).unwrap();
+	assert!(file.conflicts().is_empty());
+	assert_eq!(file.inputs, Bindings::new().inputs);
+}
+
+#[test]
+fn test_axis() {
+	let mut b = Bindings::new();
+	assert_eq!(b.axis("leftx", 100), vec![]);
+	assert_eq!(b.axis("leftx", -20000), vec![(Key::Left, true)]);
+	assert_eq!(b.axis("leftx", -30000), vec![]); // no repeats
+	assert_eq!(
+		b.axis("leftx", 20000),
+		vec![(Key::Left, false), (Key::Right, true)]
+	);
+	assert_eq!(b.axis("leftx", 0), vec![(Key::Right, false)]);
+	assert_eq!(b.axis("rightx", 20000), vec![]); // unbound
+}
+
+#[test]
+fn test_conflicts() {
+	let b: FnvHashMap<Key, Vec<String>> =
+		serde_json::from_str(r#"{"Left": ["key:S", "key:A"], "Down": ["key:S"]}"#).unwrap();
+	let b = Bindings::from_table(b.into_iter().collect()).unwrap();
+	assert_eq!(
+		b.conflicts(),
+		&["key:S is bound to both Left and Down".to_string()]
+	);
+	assert_eq!(b.key("S"), Key::Left);
+	assert_eq!(b.key("A"), Key::Left);
+}
@@ -0,0 +1,267 @@
+use crate::prelude::*;
+
+/// What to do, selected by the first command-line argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+	/// Start the front-end, or play a single level directly.
+	Play(Option<PathBuf>),
+	/// Edit a level, created if it does not exist yet.
+	Edit(PathBuf),
+	/// Ray-trace a whole level into a PNG image,
+	/// and optionally its distinct tiles into an Atlas.
+	Bake {
+		level: PathBuf,
+		out: PathBuf,
+		atlas: Option<PathBuf>,
+	},
+	/// Check a level for problems.
+	Validate(PathBuf),
+	Help,
+}
+
+/// Parsed command line: a command plus options.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+	pub command: Command,
+	pub window_size: (u32, u32),
+	pub fullscreen: bool,
+	/// Overrides DEFAULT_TEXTURE_DIR.
+	pub texture_dir: Option<PathBuf>,
+	/// Number of render threads, default: one per core but one.
+	pub threads: Option<usize>,
+	pub quality: Quality,
+	/// Game ticks per second.
+	pub tick_rate: u32,
+}
+
+pub const USAGE: &str = "usage: main [command] [options]
+
+commands:
+  play [level.json]                play a level, or start at the title screen (default)
+  edit <level.json>                edit a level
+  bake <level.json> --out <png>    render a whole level to an image
+       [--atlas <png>]             and its tiles to an atlas (with a .atlas.json index)
+  validate <level.json>            check a level for problems
+  help                             show this message
+
+options:
+  --size <width>x<height>          window size (default 960x540)
+  --fullscreen                     fullscreen window
+  --textures <dir>                 load textures from dir (default assets/textures)
+  --threads <n>                    number of render threads
+  --quality <low|medium|high>      light quality (default medium)
+  --tick-rate <n>                  game ticks per second (default 60)
+";
+
+impl Options {
+	pub const DEFAULT_WINDOW_SIZE: (u32, u32) = (1920 / 2, 1080 / 2);
+
+	/// Parse command-line arguments (without the program name).
+	pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+		let mut opts = Self {
+			command: Command::Play(None),
+			window_size: Self::DEFAULT_WINDOW_SIZE,
+			fullscreen: false,
+			texture_dir: None,
+			threads: None,
+			quality: Quality::Medium,
+			tick_rate: DEFAULT_TICK_RATE,
+		};
+		let mut out = None;
+		let mut atlas = None;
+		let mut help = false;
+		let mut positional = Vec::new();
+
+		let mut args = args.into_iter();
+		while let Some(arg) = args.next() {
+			let mut value = || match args.next() {
+				Some(v) => Ok(v),
+				None => GenError::new(format!("{} needs a value", arg)),
+			};
+			match arg.as_str() {
+				"--size" => opts.window_size = parse_size(&value()?)?,
+				"--fullscreen" => opts.fullscreen = true,
+				"--textures" => opts.texture_dir = Some(PathBuf::from(value()?)),
+				"--threads" => opts.threads = Some(parse_threads(&value()?)?),
+				"--quality" => opts.quality = value()?.parse()?,
+				"--tick-rate" => opts.tick_rate = parse_tick_rate(&value()?)?,
+				"--out" => out = Some(PathBuf::from(value()?)),
+				"--atlas" => atlas = Some(PathBuf::from(value()?)),
+				"-h" | "--help" => help = true,
+				a if a.starts_with('-') => return GenError::new(format!("unknown option: {}", a)),
+				_ => positional.push(arg),
+			}
+		}
+
+		opts.command = match help {
+			true => Command::Help,
+			false => Self::parse_command(&positional, out, atlas)?,
+		};
+		Ok(opts)
+	}
+
+	fn parse_command(
+		args: &[String],
+		out: Option<PathBuf>,
+		atlas: Option<PathBuf>,
+	) -> Result<Command> {
+		let level = |i: usize| args.get(i).map(PathBuf::from);
+		let cmd = args.first().map(String::as_str).unwrap_or("play");
+		let (cmd, nargs) = match (cmd, level(1)) {
+			("play", l) => (Command::Play(l), if args.is_empty() { 0 } else { 2 }),
+			("edit", Some(l)) => (Command::Edit(l), 2),
+			("validate", Some(l)) => (Command::Validate(l), 2),
+			("bake", Some(level)) => match out {
+				Some(out) => {
+					check_bake_paths(&level, &out, &atlas)?;
+					(Command::Bake { level, out, atlas }, 2)
+				}
+				None => return GenError::new("bake needs --out <png>".into()),
+			},
+			("help", _) => (Command::Help, 1),
+			("edit", None) | ("validate", None) | ("bake", None) => {
+				return GenError::new(format!("{} needs a level", cmd))
+			}
+			(c, _) => return GenError::new(format!("unknown command: {}", c)),
+		};
+		if args.len() > nargs {
+			return GenError::new(format!("unexpected argument: {}", args[nargs]));
+		}
+		Ok(cmd)
+	}
+
+	/// The options that apply to the whole game:
+	/// texture directory, render threads and light quality.
+	pub fn config(&self) -> Config {
+		let mut c = Config::default();
+		if let Some(dir) = &self.texture_dir {
+			c.texture_dir = dir.clone();
+		}
+		c.render = RenderOptions {
+			threads: self.threads.unwrap_or(0),
+			quality: self.quality,
+		};
+		c
+	}
+}
+
+// bake must not overwrite its level, or write the image and atlas to the same file.
+fn check_bake_paths(level: &Path, out: &Path, atlas: &Option<PathBuf>) -> Result<()> {
+	let mut outputs = vec![out.to_owned()];
+	if let Some(atlas) = atlas {
+		outputs.push(atlas.clone());
+		outputs.push(index_file(atlas));
+	}
+	for (i, p) in outputs.iter().enumerate() {
+		if p == level || outputs[..i].contains(p) {
+			return GenError::new(format!("bake would overwrite {}", p.to_string_lossy()));
+		}
+	}
+	Ok(())
+}
+
+// parse "<width>x<height>", e.g. "1920x1080".
+fn parse_size(s: &str) -> Result<(u32, u32)> {
+	let err = || GenError::new(format!("invalid size: {} (want e.g. 1920x1080)", s));
+	let mut parts = s.splitn(2, 'x');
+	match (parts.next().map(str::parse), parts.next().map(str::parse)) {
+		(Some(Ok(w)), Some(Ok(h))) if w > 0 && h > 0 => Ok((w, h)),
+		_ => err(),
+	}
+}
+
+fn parse_threads(s: &str) -> Result<usize> {
+	match s.parse() {
+		Ok(n) if n > 0 => Ok(n),
+		_ => GenError::new(format!("invalid number of threads: {}", s)),
+	}
+}
+
+fn parse_tick_rate(s: &str) -> Result<u32> {
+	match s.parse() {
+		Ok(n) if (MIN_TICK_RATE..=MAX_TICK_RATE).contains(&n) => Ok(n),
+		_ => GenError::new(format!(
+			"invalid tick rate: {} (want {}..{})",
+			s, MIN_TICK_RATE, MAX_TICK_RATE
+		)),
+	}
+}
+
+#[test]
+fn test_parse_commands() {
+	let parse = |args: &str| Options::parse(args.split_whitespace().map(String::from));
+	let p = PathBuf::from;
+	assert_eq!(parse("").unwrap().command, Command::Play(None));
+	assert_eq!(parse("play").unwrap().command, Command::Play(None));
+	assert_eq!(
+		parse("play a.json").unwrap().command,
+		Command::Play(Some(p("a.json")))
+	);
+	assert_eq!(
+		parse("edit a.json").unwrap().command,
+		Command::Edit(p("a.json"))
+	);
+	assert_eq!(
+		parse("validate a.json").unwrap().command,
+		Command::Validate(p("a.json"))
+	);
+	assert_eq!(
+		parse("bake a.json --out a.png").unwrap().command,
+		Command::Bake {
+			level: p("a.json"),
+			out: p("a.png"),
+			atlas: None,
+		}
+	);
+	assert_eq!(
+		parse("bake --atlas t.png a.json --out a.png")
+			.unwrap()
+			.command,
+		Command::Bake {
+			level: p("a.json"),
+			out: p("a.png"),
+			atlas: Some(p("t.png")),
+		}
+	);
+	assert_eq!(parse("--help").unwrap().command, Command::Help);
+	assert_eq!(parse("edit a.json -h").unwrap().command, Command::Help);
+
+	assert!(parse("edit").is_err());
+	assert!(parse("bake a.json").is_err());
+	assert!(parse("play a.json b.json").is_err());
+	assert!(parse("bake a.json --out a.json").is_err());
+	assert!(parse("bake a.json --out a.png --atlas a.png").is_err());
+	assert!(parse("bake a.atlas.json --out a.png --atlas a.png").is_err());
+	assert!(parse("frobnicate").is_err());
+}
+
+#[test]
+fn test_parse_options() {
+	let parse = |args: &str| Options::parse(args.split_whitespace().map(String::from));
+	let o =
+		parse("play --size 640x480 --fullscreen --textures tex --threads 3 --quality low").unwrap();
+	assert_eq!(o.window_size, (640, 480));
+	assert!(o.fullscreen);
+	assert_eq!(o.texture_dir, Some(PathBuf::from("tex")));
+	assert_eq!(o.threads, Some(3));
+	assert_eq!(o.quality, Quality::Low);
+	let c = o.config();
+	assert_eq!(c.texture_dir, PathBuf::from("tex"));
+	assert_eq!((c.render.threads, c.render.quality), (3, Quality::Low));
+
+	let o = parse("").unwrap();
+	assert_eq!(o.window_size, Options::DEFAULT_WINDOW_SIZE);
+	assert_eq!(o.quality, Quality::Medium);
+	assert_eq!(o.config(), Config::default());
+
+	assert!(parse("--size 640").is_err());
+	assert!(parse("--size 0x480").is_err());
+	assert!(parse("--threads 0").is_err());
+	assert_eq!(parse("--tick-rate 120").unwrap().tick_rate, 120);
+	assert_eq!(parse("").unwrap().tick_rate, DEFAULT_TICK_RATE);
+	assert!(parse("--tick-rate 10").is_err());
+	assert!(parse("--tick-rate fast").is_err());
+	assert!(parse("--quality ultra").is_err());
+	assert!(parse("--threads").is_err());
+	assert!(parse("--bogus").is_err());
+}
@@ -0,0 +1,18 @@
+use crate::prelude::*;
+
+/// Settings for the whole game, from the command line (see Options):
+/// where textures are loaded from and how tiles are rendered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+	pub texture_dir: PathBuf,
+	pub render: RenderOptions,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			texture_dir: PathBuf::from(DEFAULT_TEXTURE_DIR),
+			render: RenderOptions::default(),
+		}
+	}
+}
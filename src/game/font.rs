@@ -0,0 +1,162 @@
+use crate::prelude::*;
+
+/// Built-in bitmap font for the HUD and debug overlay.
+/// Each glyph is a texture of GLYPH_W x GLYPH_H pixels,
+/// drawn with a one-pixel drop shadow so that text is readable on any background.
+pub struct Font {
+	glyphs: Vec<Texture>,
+	shadows: Vec<Texture>,
+}
+
+/// Glyph size, in font pixels (before scaling).
+pub const GLYPH_W: i32 = 5;
+pub const GLYPH_H: i32 = 7;
+
+/// Distance between glyphs, in font pixels.
+const ADVANCE: i32 = GLYPH_W + 1;
+const LINE_HEIGHT: i32 = GLYPH_H + 2;
+
+impl Font {
+	/// The built-in font, in the given color.
+	pub fn new(color: BGRA) -> Self {
+		Self {
+			glyphs: GLYPHS
+				.iter()
+				.map(|(_, g)| Texture::new(glyph_image(g, color)))
+				.collect(),
+			shadows: GLYPHS
+				.iter()
+				.map(|(_, g)| Texture::new(glyph_image(g, BGRA(0, 0, 0, 160))))
+				.collect(),
+		}
+	}
+
+	/// Size of text drawn at given scale, in pixels. Lines are separated by '\n'.
+	pub fn text_dimensions(text: &str, scale: i32) -> (i32, i32) {
+		let cols = text.lines().map(|l| l.chars().count()).max().unwrap_or(0) as i32;
+		let rows = text.lines().count() as i32;
+		(cols * ADVANCE * scale, rows * LINE_HEIGHT * scale)
+	}
+
+	/// Glyph textures (glyph, shadow) for each character,
+	/// with positions in pixels relative to the text's top-left corner.
+	/// Lower case is drawn as upper case, unknown characters as '?'.
+	pub fn layout(&self, text: &str, scale: i32) -> Vec<(&Texture, &Texture, Pt)> {
+		let mut v = Vec::with_capacity(text.len());
+		for (row, line) in text.lines().enumerate() {
+			for (col, c) in line.chars().enumerate() {
+				if c == ' ' {
+					continue;
+				}
+				let i = glyph_index(c);
+				let pos = Pt(col as i32 * ADVANCE, row as i32 * LINE_HEIGHT) * scale;
+				v.push((&self.glyphs[i], &self.shadows[i], pos));
+			}
+		}
+		v
+	}
+}
+
+fn glyph_index(c: char) -> usize {
+	let c = c.to_ascii_uppercase();
+	GLYPHS
+		.iter()
+		.position(|(g, _)| *g == c)
+		.unwrap_or_else(|| glyph_index('?'))
+}
+
+fn glyph_image(rows: &[u8; GLYPH_H as usize], color: BGRA) -> Image<BGRA> {
+	Image::from_fn((GLYPH_W, GLYPH_H), |x, y| {
+		if rows[y as usize] & (1 << (GLYPH_W - 1 - x)) != 0 {
+			color
+		} else {
+			BGRA(0, 0, 0, 0)
+		}
+	})
+}
+
+// 5x7 glyphs, one byte per row, most significant (of 5) bits on the left.
+#[rustfmt::skip]
+const GLYPHS: &[(char, [u8; GLYPH_H as usize])] = &[
+	(' ', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+	('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+	('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+	('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+	('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+	('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+	('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+	('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+	('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+	('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+	('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+	('A', [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11]),
+	('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+	('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+	('D', [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C]),
+	('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+	('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+	('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+	('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+	('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+	('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+	('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+	('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+	('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+	('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+	('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+	('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+	('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+	('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+	('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+	('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+	('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+	('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+	('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+	('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+	('Y', [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04]),
+	('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+	('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+	(',', [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08]),
+	(':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+	('!', [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04]),
+	('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+	('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+	('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+	('=', [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00]),
+	('*', [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00]),
+	('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+	('%', [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03]),
+	('#', [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A]),
+	('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+	(')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+	('[', [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E]),
+	(']', [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E]),
+	('<', [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02]),
+	('>', [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08]),
+	('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+	('\'', [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00]),
+	('"', [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00]),
+];
+
+#[test]
+fn test_font() {
+	let font = Font::new(BGRA(255, 255, 255, 255));
+	assert_eq!(
+		Font::text_dimensions("AB\nC", 2),
+		(2 * ADVANCE * 2, 2 * LINE_HEIGHT * 2)
+	);
+
+	// spaces are skipped, lower case is upper case, unknown is '?'
+	let l = font.layout("a b\n~", 1);
+	assert_eq!(l.len(), 3);
+	assert_eq!(l[0].0.uid(), font.glyphs[glyph_index('A')].uid());
+	assert_eq!(l[1].2, Pt(2 * ADVANCE, 0));
+	assert_eq!(l[2].0.uid(), font.glyphs[glyph_index('?')].uid());
+	assert_eq!(l[2].2, Pt(0, LINE_HEIGHT));
+
+	// pixels of 'L': left column and bottom row.
+	let img = glyph_image(&GLYPHS[glyph_index('L')].1, BGRA(1, 2, 3, 4));
+	assert_eq!(img.at((0, 0)), BGRA(1, 2, 3, 4));
+	assert_eq!(img.at((1, 0)), BGRA(0, 0, 0, 0));
+	assert_eq!(img.at((4, 6)), BGRA(1, 2, 3, 4));
+}
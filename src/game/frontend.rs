@@ -0,0 +1,310 @@
+use crate::prelude::*;
+use std::fs;
+
+/// The game as seen by players (as opposed to the Editor):
+/// title screen, level select, playing, pause menu and level complete screen.
+pub struct Frontend {
+	screen: Screen,
+	menu: Menu,
+	levels: Vec<PathBuf>,
+	// level being played (index in levels), if any.
+	game: Option<(usize, GameState)>,
+	config: Config,
+	font: Font,
+	tick_rate: u32,
+	quit: bool,
+	// message shown at the bottom of the screen for this many more ticks.
+	warning: Option<(String, i32)>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Screen {
+	Title,
+	LevelSelect,
+	Playing,
+	Paused,
+	Complete,
+}
+
+impl Frontend {
+	/// Front-end offering the levels in a directory, e.g. levels_dir().
+	pub fn new(dir: &Path, config: Config) -> Result<Self> {
+		Ok(Self::with_levels(list_levels(dir)?, config))
+	}
+
+	pub fn with_levels(levels: Vec<PathBuf>, config: Config) -> Self {
+		Self {
+			screen: Screen::Title,
+			menu: Self::title_menu(),
+			levels,
+			game: None,
+			config,
+			font: Font::new(BGRA(255, 255, 255, 255)),
+			tick_rate: DEFAULT_TICK_RATE,
+			quit: false,
+			warning: None,
+		}
+	}
+
+	/// Front-end that starts playing a single level right away,
+	/// returning to the title screen when quitting the level.
+	pub fn play_level(level: PathBuf, config: Config) -> Self {
+		let mut f = Self::with_levels(vec![level], config);
+		f.play(0);
+		f
+	}
+
+	fn title_menu() -> Menu {
+		Menu::new("HAMSTR", &["PLAY", "SELECT LEVEL", "QUIT"])
+	}
+
+	fn level_select_menu(&self) -> Menu {
+		let mut items: Vec<String> = self.levels.iter().map(|p| level_name(p)).collect();
+		items.push("BACK".into());
+		Menu::with_items("SELECT LEVEL", items)
+	}
+
+	fn pause_menu() -> Menu {
+		Menu::new("PAUSED", &["RESUME", "RESTART", "QUIT TO MENU"])
+	}
+
+	fn complete_menu(&self, level: usize) -> Menu {
+		if level + 1 < self.levels.len() {
+			Menu::new("LEVEL COMPLETE", &["NEXT LEVEL", "QUIT TO MENU"])
+		} else {
+			Menu::new("ALL LEVELS COMPLETE", &["QUIT TO MENU"])
+		}
+	}
+
+	fn show(&mut self, screen: Screen) {
+		self.menu = match screen {
+			Screen::Title | Screen::Playing => Self::title_menu(),
+			Screen::LevelSelect => self.level_select_menu(),
+			Screen::Paused => Self::pause_menu(),
+			Screen::Complete => self.complete_menu(self.level()),
+		};
+		self.screen = screen;
+	}
+
+	fn level(&self) -> usize {
+		self.game.as_ref().map(|g| g.0).unwrap_or(0)
+	}
+
+	// Load a level and start playing it.
+	// On failure, stay in the menus.
+	fn play(&mut self, level: usize) {
+		if level >= self.levels.len() {
+			self.warn("no levels found");
+			return;
+		}
+		match self.load_game(level) {
+			Ok(game) => {
+				self.game = Some((level, game));
+				self.show(Screen::Playing);
+			}
+			Err(e) => {
+				let msg = format!("loading {}: {}", level_name(&self.levels[level]), e);
+				self.warn(&msg);
+			}
+		}
+	}
+
+	fn load_game(&self, level: usize) -> Result<GameState> {
+		let data = LevelData::load(&self.levels[level])?;
+		let goodies = data.goodies_map();
+		let map = Map::from(data.map_bytes, goodies, data.lights, &self.config)?;
+		let physics = match &data.physics {
+			Some(o) => o.apply(&Physics::load_default())?,
+			None => Physics::load_default(),
+		};
+		let mut game = GameState::new(map, physics, &self.config.texture_dir);
+		game.set_tick_rate(self.tick_rate);
+		game.set_reset_goodies(data.reset_goodies);
+		Ok(game)
+	}
+
+	fn quit_to_menu(&mut self) {
+		self.game = None;
+		self.show(Screen::Title);
+	}
+
+	fn game_mut(&mut self) -> &mut GameState {
+		&mut self.game.as_mut().unwrap().1
+	}
+
+	// ------------------------------------------------------------------------------ events
+
+	fn key_down_menu(&mut self, k: Key) {
+		let choice = match self.menu.key_down(k) {
+			None => return,
+			Some(i) => i,
+		};
+		match (self.screen, choice) {
+			(Screen::Title, 0) => self.play(0),
+			(Screen::Title, 1) => self.show(Screen::LevelSelect),
+			(Screen::Title, _) => self.quit = true,
+			(Screen::LevelSelect, i) if i < self.levels.len() => self.play(i),
+			(Screen::LevelSelect, _) => self.show(Screen::Title),
+			(Screen::Paused, 0) => self.show(Screen::Playing),
+			(Screen::Paused, 1) => {
+				self.game_mut().restart();
+				self.show(Screen::Playing);
+			}
+			(Screen::Paused, _) => self.quit_to_menu(),
+			(Screen::Complete, 0) if self.level() + 1 < self.levels.len() => {
+				self.play(self.level() + 1)
+			}
+			(Screen::Complete, _) => self.quit_to_menu(),
+			(Screen::Playing, _) => (),
+		}
+	}
+
+	// Key::Pause goes back: pauses the game, resumes it, or leaves the level select.
+	fn back(&mut self) {
+		match self.screen {
+			Screen::Playing => self.show(Screen::Paused),
+			Screen::Paused => self.show(Screen::Playing),
+			Screen::LevelSelect => self.show(Screen::Title),
+			Screen::Title | Screen::Complete => (),
+		}
+	}
+
+	// ------------------------------------------------------------------------------ draw
+
+	fn draw_warning(&self, disp: &mut SDLDisplay) {
+		if let Some((msg, _)) = &self.warning {
+			let (_, h) = disp.dimensions();
+			let (_, th) = Font::text_dimensions(msg, 2);
+			disp.draw_text(&self.font, msg, Pt(8, h - th - 8), 2);
+		}
+	}
+
+	const BG: BGRA = BGRA(80, 50, 40, 255);
+	const DIM: BGRA = BGRA(0, 0, 0, 160);
+	const WARNING_TICKS: i32 = 300;
+}
+
+impl App for Frontend {
+	fn tick(&mut self) {
+		if let Some((_, t)) = &mut self.warning {
+			*t -= 1;
+			if *t <= 0 {
+				self.warning = None;
+			}
+		}
+
+		if self.screen == Screen::Playing {
+			self.game_mut().tick();
+			if self.game_mut().is_complete() {
+				self.show(Screen::Complete);
+			}
+		}
+	}
+
+	fn draw(&self, disp: &mut SDLDisplay, alpha: f64) {
+		match self.screen {
+			Screen::Title | Screen::LevelSelect => {
+				let dim = disp.dimensions();
+				disp.fill_rect(Self::BG, Pt(0, 0), dim);
+				self.menu.draw(disp, &self.font);
+			}
+			Screen::Playing => self.game.as_ref().unwrap().1.draw(disp, alpha),
+			Screen::Paused | Screen::Complete => {
+				// frozen game in the background
+				self.game.as_ref().unwrap().1.draw(disp, 1.0);
+				let dim = disp.dimensions();
+				disp.fill_rect(Self::DIM, Pt(0, 0), dim);
+				self.menu.draw(disp, &self.font);
+			}
+		}
+		self.draw_warning(disp);
+	}
+
+	fn key_down(&mut self, k: Key) {
+		match (self.screen, k) {
+			(_, Key::Pause) => self.back(),
+			(Screen::Playing, _) => self.game_mut().key_down(k),
+			_ => self.key_down_menu(k),
+		}
+	}
+
+	fn key_up(&mut self, k: Key) {
+		if self.screen == Screen::Playing {
+			self.game_mut().key_up(k);
+		}
+	}
+
+	fn warn(&mut self, msg: &str) {
+		eprintln!("warning: {}", msg);
+		self.warning = Some((msg.into(), Self::WARNING_TICKS));
+	}
+
+	fn set_tick_rate(&mut self, tick_rate: u32) {
+		self.tick_rate = tick_rate;
+		if let Some((_, game)) = &mut self.game {
+			game.set_tick_rate(tick_rate);
+		}
+	}
+
+	fn quit_requested(&self) -> bool {
+		self.quit
+	}
+}
+
+/// Directory with the levels offered by the level select.
+pub fn levels_dir() -> PathBuf {
+	PathBuf::from("assets/levels")
+}
+
+/// The level files (*.json) in a directory, sorted by name.
+pub fn list_levels(dir: &Path) -> Result<Vec<PathBuf>> {
+	let mut levels = Vec::new();
+	for entry in fs::read_dir(dir)? {
+		let p = entry?.path();
+		if p.extension().map(|e| e == "json").unwrap_or(false) {
+			levels.push(p);
+		}
+	}
+	levels.sort();
+	Ok(levels)
+}
+
+/// Name of a level to show in menus: file name without extension.
+pub fn level_name(p: &Path) -> String {
+	p.file_stem()
+		.unwrap_or_default()
+		.to_string_lossy()
+		.to_uppercase()
+}
+
+#[test]
+fn test_list_levels() {
+	let levels = list_levels(&levels_dir()).unwrap();
+	assert!(levels.contains(&levels_dir().join("welcome.json")));
+	assert!(levels.windows(2).all(|w| w[0] < w[1]));
+	assert_eq!(level_name(&levels_dir().join("welcome.json")), "WELCOME");
+}
+
+#[test]
+fn test_frontend_menus() {
+	let levels = vec![PathBuf::from("a.json"), PathBuf::from("b.json")];
+	let mut f = Frontend::with_levels(levels, Config::default());
+
+	// title -> level select -> back
+	f.key_down(Key::Down);
+	f.key_down(Key::A);
+	assert_eq!(f.screen, Screen::LevelSelect);
+	assert_eq!(f.menu.text(), "> A\n  B\n  BACK\n");
+	f.key_down(Key::Pause);
+	assert_eq!(f.screen, Screen::Title);
+
+	// a level that fails to load keeps us in the menu, with a warning.
+	f.key_down(Key::A);
+	assert_eq!(f.screen, Screen::Title);
+	assert!(f.warning.is_some());
+
+	// quit
+	f.key_down(Key::Up);
+	f.key_down(Key::A);
+	assert!(f.quit_requested());
+}
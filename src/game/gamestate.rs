@@ -10,28 +10,130 @@ pub struct GameState {
 	time: i32,
 	key_debouncer: KeyDebouncer,
 	view_center: Pt,
+	prev_view_center: Pt,
+	crumbling: FnvHashMap<Pt, Crumble>,
+
+	// respawn position: level start or latest checkpoint.
+	spawn: Pt,
+	// goodies collected since the latest checkpoint,
+	// put back on respawn if reset_goodies is set.
+	collected: Vec<(Pt, u8)>,
+	reset_goodies: bool,
+
+	// HUD
+	font: Font,
+	seeds: i32,
+	lives: i32,
+	start_time: i32,
+	tick_rate: u32,
+	// goodies at the start of the level, restored after game over.
+	initial_goodies: FnvHashMap<Pt, u8>,
+	show_debug: bool,
+}
+
+/// A crumbling block that has been stood on.
+/// It disappears from the map CRUMBLE_TICKS after being stood on,
+/// and re-appears RESPAWN_TICKS later (both at DEFAULT_TICK_RATE).
+#[derive(Copy, Clone, Debug)]
+struct Crumble {
+	blk: u8,    // original block, to be restored
+	since: i32, // time of being stood on, or of disappearing
+	gone: bool,
 }
 
 impl GameState {
-	pub fn new(map: Map) -> Self {
+	/// Game on map, with the hamster sprites loaded from texture_dir.
+	pub fn new(map: Map, physics: Physics, texture_dir: &Path) -> Self {
+		let mut hamster = Hamster::new(Self::LEVEL_SPAWN, texture_dir);
+		hamster.set_physics(physics);
+		Self::with_hamster(map, hamster)
+	}
+
+	/// Like new, with a given hamster (e.g. with test sprites), placed at the level start.
+	pub fn with_hamster(map: Map, mut hamster: Hamster) -> Self {
 		map.warmup_cache();
+		let initial_goodies = map.goodies().clone();
+		hamster.respawn(Self::LEVEL_SPAWN);
+		hamster.set_lights(&map.lights());
 		Self {
 			map,
-			hamster: Hamster::new(Pt(2, 2) * GRID),
+			hamster,
 			time: 0,
 			key_debouncer: KeyDebouncer::new(),
 			view_center: Pt(0, 0),
+			prev_view_center: Pt(0, 0),
+			crumbling: FnvHashMap::default(),
+			spawn: Self::LEVEL_SPAWN,
+			collected: Vec::new(),
+			reset_goodies: false,
+			font: Font::new(BGRA(255, 255, 255, 255)),
+			seeds: 0,
+			lives: Self::START_LIVES,
+			start_time: 0,
+			tick_rate: DEFAULT_TICK_RATE,
+			initial_goodies,
+			show_debug: false,
 		}
 	}
 
+	const START_LIVES: i32 = 3;
+
+	/// Ticks per second the game runs at.
+	/// The hamster's physics and the game's timers are scaled to it,
+	/// so that the game plays the same at any tick rate.
+	pub fn set_tick_rate(&mut self, tick_rate: u32) {
+		self.tick_rate = tick_rate;
+		self.hamster.set_tick_rate(tick_rate);
+	}
+
+	/// Re-light the hamster's sprites, e.g. after the level's lights were reloaded.
+	/// (The tiles are lit by the map's renderer.)
+	pub fn set_lights(&mut self, lights: &Lights) {
+		self.hamster.set_lights(lights);
+	}
+
+	// Number of ticks lasting as long as default_ticks at DEFAULT_TICK_RATE.
+	fn ticks(&self, default_ticks: i32) -> i32 {
+		default_ticks * self.tick_rate as i32 / DEFAULT_TICK_RATE as i32
+	}
+
+	/// Where the hamster starts, and respawns if no checkpoint was reached.
+	const LEVEL_SPAWN: Pt = Pt(2 * GRID as i32, 2 * GRID as i32);
+
+	/// Put goodies collected since the latest checkpoint back when the hamster dies?
+	pub fn set_reset_goodies(&mut self, reset: bool) {
+		self.reset_goodies = reset;
+	}
+
 	pub fn set_view_center(&mut self, center: Pt) {
 		self.view_center = center;
+		self.prev_view_center = center;
+	}
+
+	/// Change the physics while playing (hot-reload).
+	pub fn set_physics(&mut self, physics: Physics) {
+		self.hamster.set_physics(physics);
 	}
 
 	// ------------------------------------------------------------------------------ draw
 
-	pub fn draw(&self, disp: &mut SDLDisplay) {
-		let mut disp = Viewport::with_center(disp, self.view_center);
+	/// Draw at a fraction alpha (0..1) of the way between the previous and current tick,
+	/// for smooth motion on displays that refresh faster than the tick rate.
+	pub fn draw(&self, disp: &mut SDLDisplay, alpha: f64) {
+		self.draw_world(
+			&mut Viewport::with_center(
+				disp,
+				interpolate(self.prev_view_center, self.view_center, alpha),
+			),
+			alpha,
+		);
+		self.draw_hud(disp);
+		if self.show_debug {
+			self.draw_debug(disp);
+		}
+	}
+
+	fn draw_world(&self, disp: &mut Viewport, alpha: f64) {
 		disp.clear(BGRA(255, 210, 210, 255));
 
 		let grid = GRID as i32;
@@ -48,7 +150,50 @@ impl GameState {
 			}
 		}
 
-		self.hamster.draw(&mut disp, self.time);
+		self.hamster.draw(disp, self.time, alpha);
+	}
+
+	const HUD_SCALE: i32 = 3;
+	const HUD_MARGIN: i32 = 8;
+
+	// seeds, lives and time played, along the top of the screen.
+	fn draw_hud(&self, disp: &mut SDLDisplay) {
+		let (w, _) = disp.dimensions();
+		let m = Self::HUD_MARGIN;
+		let s = Self::HUD_SCALE;
+
+		let seeds = format!("SEEDS {}", self.seeds);
+		disp.draw_text(&self.font, &seeds, Pt(m, m), s);
+
+		let lives = format!("LIVES {}", self.lives);
+		let (lw, _) = Font::text_dimensions(&lives, s);
+		disp.draw_text(&self.font, &lives, Pt((w - lw) / 2, m), s);
+
+		let secs = (self.time - self.start_time) / self.tick_rate as i32;
+		let time = format!("{}:{:02}", secs / 60, secs % 60);
+		let (tw, _) = Font::text_dimensions(&time, s);
+		disp.draw_text(&self.font, &time, Pt(w - tw - m, m), s);
+	}
+
+	// frame rate, renderer and hamster state, toggled with Key::Debug.
+	fn draw_debug(&self, disp: &mut SDLDisplay) {
+		let stats = self.map.render_stats();
+		let mut text = format!(
+			"FPS {:.0} DROPPED TICKS {}\nBAKING {} CACHED {} ERRORS {}\nQUEUE {} BUSY {:.0}%\n",
+			disp.fps(),
+			disp.dropped_ticks(),
+			stats.queued,
+			stats.cached,
+			stats.errors,
+			stats.queue_depth,
+			100.0 * stats.utilization
+		);
+		for l in self.hamster.debug_lines() {
+			text.push_str(&l);
+			text.push('\n');
+		}
+		let m = Self::HUD_MARGIN;
+		disp.draw_text(&self.font, &text, Pt(m, m + 12 * Self::HUD_SCALE), 2);
 	}
 
 	pub fn visible_blocks(center: Pt, disp_dim: (i32, i32)) -> ((i32, i32), (i32, i32)) {
@@ -69,9 +214,6 @@ impl GameState {
 
 	pub fn tick(&mut self) {
 		self.time += 1;
-		if self.time % 16 == 0 {
-			self.print_stats();
-		}
 
 		let keys = self.key_debouncer.key_states();
 		self.key_debouncer.clear();
@@ -83,6 +225,7 @@ impl GameState {
 	}
 
 	fn update_view_center(&mut self) {
+		self.prev_view_center = self.view_center;
 		// lookahead
 		self.view_center.0 += (self.hamster.speed().0 * 3) / 2;
 
@@ -93,10 +236,148 @@ impl GameState {
 		self.view_center.1 = clamp(self.view_center.1, ham.1 - D, ham.1 + D);
 	}
 
+	// Number of ticks (at DEFAULT_TICK_RATE) the death animation plays before respawning.
+	const DEATH_TICKS: i32 = 40;
+
 	fn handle_triggers(&mut self) {
+		self.update_crumbling();
+
+		if let Some(t) = self.hamster.dead_since() {
+			if self.time - t > self.ticks(Self::DEATH_TICKS) {
+				self.respawn();
+			}
+			return;
+		}
+
 		let grid = self.hamster.center() / GRID;
-		if self.map.goodie_at(grid) != 0 {
-			self.map.set_goodie(grid, 0)
+		let goodie = self.map.goodie_at(grid);
+		if goodie != 0 {
+			self.map.set_goodie(grid, 0);
+			self.collected.push((grid, goodie));
+			self.seeds += 1;
+		}
+
+		if self.map.type_at(grid) == BlockTyp::Checkpoint {
+			self.spawn = grid * GRID;
+			self.collected.clear();
+		}
+
+		if self.hamster.touches_deadly(&self.map) {
+			self.hamster.kill(self.time);
+		}
+
+		self.trigger_crumbling();
+	}
+
+	// Bring the hamster back to life at the latest checkpoint,
+	// and restore the parts of the level it has destroyed.
+	// After losing the last life, the level starts over.
+	fn respawn(&mut self) {
+		self.lives -= 1;
+		if self.lives == 0 {
+			self.game_over();
+		}
+		self.revive();
+	}
+
+	/// Start the level over, e.g. from the pause menu.
+	pub fn restart(&mut self) {
+		self.game_over();
+		self.revive();
+	}
+
+	/// Have all seeds in the level been collected?
+	/// (Levels without seeds are never complete).
+	pub fn is_complete(&self) -> bool {
+		!self.initial_goodies.is_empty() && self.map.goodies().is_empty()
+	}
+
+	fn revive(&mut self) {
+		self.hamster.respawn(self.spawn);
+		if self.reset_goodies {
+			for (p, g) in self.collected.drain(..) {
+				self.map.set_goodie(p, g);
+				self.seeds -= 1;
+			}
+		}
+		self.collected.clear();
+		// crumbled blocks overlapping the hamster come back when it has moved away
+		// (see update_crumbling), so that it does not respawn inside a block.
+		let hamster = self.hamster.rect();
+		let map = &mut self.map;
+		self.crumbling.retain(|&p, c| {
+			let block = Rect::new(p * GRID, (GRID as i32, GRID as i32));
+			if c.gone && hamster.overlaps(&block) {
+				return true;
+			}
+			if c.gone {
+				map.set(p, c.blk);
+			}
+			false
+		});
+	}
+
+	fn game_over(&mut self) {
+		self.lives = Self::START_LIVES;
+		self.spawn = Self::LEVEL_SPAWN;
+		self.collected.clear();
+		for (&p, &g) in &self.initial_goodies {
+			self.map.set_goodie(p, g);
+		}
+		self.seeds = 0;
+		self.start_time = self.time;
+	}
+
+	// ------------------------------------------------------------------------------------ crumbling
+
+	const CRUMBLE_TICKS: i32 = 30;
+	const RESPAWN_TICKS: i32 = 120;
+
+	// Start crumbling the blocks the hamster is standing on, if they are crumbly.
+	fn trigger_crumbling(&mut self) {
+		if !self.hamster.onfeet(&self.map) {
+			return;
+		}
+		for &p in &self.hamster.floor_blocks() {
+			if self.map.type_at(p) == BlockTyp::Crumbling && !self.crumbling.contains_key(&p) {
+				let blk = self.map.bytemap().at(p);
+				let since = self.time;
+				self.crumbling.insert(
+					p,
+					Crumble {
+						blk,
+						since,
+						gone: false,
+					},
+				);
+			}
+		}
+	}
+
+	// Remove crumbled blocks from the map, and restore them later.
+	fn update_crumbling(&mut self) {
+		let now = self.time;
+		let (crumble_ticks, respawn_ticks) = (
+			self.ticks(Self::CRUMBLE_TICKS),
+			self.ticks(Self::RESPAWN_TICKS),
+		);
+		let hamster = self.hamster.rect();
+		let mut restored = Vec::new();
+		for (&p, c) in self.crumbling.iter_mut() {
+			if !c.gone && now - c.since > crumble_ticks {
+				self.map.set(p, 0);
+				c.gone = true;
+				c.since = now;
+			}
+			// don't restore a block on top of the hamster.
+			let block = Rect::new(p * GRID, (GRID as i32, GRID as i32));
+			if c.gone && now - c.since > respawn_ticks && !hamster.overlaps(&block) {
+				self.map.set(p, c.blk);
+				restored.push(p);
+			}
+		}
+		for p in restored {
+			self.crumbling.remove(&p);
 		}
 	}
 
@@ -109,19 +390,138 @@ impl GameState {
 	pub fn mouse_wheel(&mut self, _x: i32, _y: i32) {}
 
 	pub fn key_down(&mut self, k: Key) {
+		if k == Key::Debug {
+			self.show_debug = !self.show_debug;
+		}
 		self.key_debouncer.key_down(k);
 	}
 
 	pub fn key_up(&mut self, k: Key) {
 		self.key_debouncer.key_up(k);
 	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Game from ASCII art: '#' brick, 'L' lava, 'C' checkpoint, 'c' crumbling, 'g' goody,
+	// anything else empty. The hamster starts in block (2, 2).
+	fn test_game(rows: &[&str]) -> GameState {
+		let mut bytes = ByteMap::new();
+		let mut goodies = FnvHashMap::default();
+		for (y, row) in rows.iter().enumerate() {
+			for (x, c) in row.chars().enumerate() {
+				let p = Pt(x as i32, y as i32);
+				let blk = match c {
+					'#' => 1,
+					'L' => 30,
+					'C' => 34,
+					'c' => 31,
+					_ => 0,
+				};
+				bytes.set(p, blk);
+				if c == 'g' {
+					goodies.insert(p, 28);
+				}
+			}
+		}
+		let g = GRID as i32;
+		let palette = (0..ED_PALETTE.len())
+			.map(|_| Surface::new(Image::new((g, g)), Image::new((g, g))))
+			.collect();
+		let mut lights = Lights::new();
+		lights.sun_rays = 1;
+		lights.ambient_rays = 1;
+		let map = Map::with_palette(bytes, goodies, lights, palette, RenderOptions::default());
+		let sprites = Rc::new(SpriteSheet::still(Image::new((32, 32))));
+		GameState::with_hamster(map, Hamster::with_sprites(Pt(0, 0), sprites))
+	}
+
+	fn run(game: &mut GameState, ticks: i32) {
+		for _ in 0..ticks {
+			game.tick();
+		}
+	}
+
+	// Walk right until the hamster dies.
+	fn walk_into_death(game: &mut GameState) {
+		game.key_down(Key::Right);
+		for _ in 0..1000 {
+			game.tick();
+			if game.hamster.dead_since().is_some() {
+				game.key_up(Key::Right);
+				return;
+			}
+		}
+		panic!("hamster did not die");
+	}
+
+	const LEVEL: &[&str] = &["", "", "", "....g.C.g.L", "###########"];
+
+	#[test]
+	fn test_death_and_checkpoint() {
+		let g = GRID as i32;
+		let mut game = test_game(LEVEL);
+		walk_into_death(&mut game);
+		assert_eq!(game.lives, GameState::START_LIVES);
+
+		run(&mut game, GameState::DEATH_TICKS + 1);
+		assert_eq!(game.hamster.dead_since(), None);
+		assert_eq!(game.lives, GameState::START_LIVES - 1);
+		assert_eq!(game.hamster.pos(), Pt(6 * g, 3 * g)); // at the checkpoint
+
+		// both goodies stay collected by default.
+		assert_eq!(game.seeds, 2);
+		assert_eq!(game.map.goodie_at(Pt(8, 3)), 0);
+
+		// game over: back to the start of the level, with all goodies.
+		for _ in 1..GameState::START_LIVES {
+			walk_into_death(&mut game);
+			run(&mut game, GameState::DEATH_TICKS + 1);
+		}
+		assert_eq!(game.lives, GameState::START_LIVES);
+		assert_eq!(game.hamster.pos(), GameState::LEVEL_SPAWN);
+		assert_eq!(game.seeds, 0);
+		assert_eq!(game.map.goodies().len(), 2);
+	}
+
+	#[test]
+	fn test_reset_goodies() {
+		let mut game = test_game(LEVEL);
+		game.set_reset_goodies(true);
+		walk_into_death(&mut game);
+		assert_eq!(game.seeds, 2);
+
+		// the goody collected after the checkpoint is back, the one before it is not.
+		run(&mut game, GameState::DEATH_TICKS + 1);
+		assert_eq!(game.seeds, 1);
+		assert_eq!(game.map.goodie_at(Pt(4, 3)), 0);
+		assert_eq!(game.map.goodie_at(Pt(8, 3)), 28);
+	}
 
-	// ----------------------------------------------------------------------------- stats
+	#[test]
+	fn test_no_respawn_inside_crumble() {
+		// a crumbled block where the hamster respawns.
+		let mut game = test_game(&["", "", "c", "", "#####"]);
+		let p = Pt(2, 2);
+		game.map.set(p, 0);
+		game.crumbling.insert(
+			p,
+			Crumble {
+				blk: 31,
+				since: -1000,
+				gone: true,
+			},
+		);
+		game.hamster.kill(game.time);
+		run(&mut game, GameState::DEATH_TICKS + 1);
+		assert_eq!(game.hamster.dead_since(), None);
+		assert_eq!(game.map.bytemap().at(p), 0);
 
-	pub fn print_stats(&self) {
-		use std::io::Write;
-		std::io::stdout().write_all(b"\x1B[2J\x1B[H").unwrap();
-		self.hamster.print_stats();
-		self.map.print_stats();
+		// restored after the hamster has fallen out of it.
+		run(&mut game, 60);
+		assert!(game.hamster.pos().y() > 3 * GRID as i32);
+		assert_eq!(game.map.bytemap().at(p), 31);
 	}
 }
@@ -1,41 +1,92 @@
 use crate::prelude::*;
 
 pub struct Hamster {
-	pos: Pt,
 	/// absolute position of top left corner, pixels
+	pos: Pt,
+	/// position before the latest tick, for drawing in between ticks
+	prev_pos: Pt,
+	/// fractional part of the position, 0..SUBPIX sub-pixels
+	subpix: Pt,
+	/// vertical speed, sub-pixels per tick
 	v_speed_unclamped: i32,
 	jump_state: JumpState,
+	/// horizontal speed, sub-pixels per tick
 	h_speed: i32,
 	look_left: bool,
+	// profile scaled to the tick rate, and as set (see set_physics, set_tick_rate).
+	physics: Physics,
+	profile: Physics,
+	tick_rate: u32,
 
-	textures: [Texture; 2],
+	anim: Animation,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 enum JumpState {
 	Standing,
+	Crouching,
 	JumpingSince(i32),
+	WallJumpingSince(i32),
+	BouncingSince(i32),
 	Falling,
+	Dropping,         // falling through a ledge
+	WallSliding(i32), // against a wall in direction -1 (left) or 1 (right)
+	Climbing,
 	Landed,
+	DeadSince(i32),
 }
 
 use JumpState::*;
 
 impl Hamster {
-	pub fn new(pos: Pt) -> Self {
+	/// Animation clips played by the hamster (see update_animation),
+	/// its sprite sheet must have them all.
+	pub const CLIPS: [&'static str; 8] = [
+		"idle", "walk", "jump", "fall", "land", "crouch", "climb", "dead",
+	];
+
+	/// Hamster with the sprite sheet from texture_dir.
+	pub fn new(pos: Pt, texture_dir: &Path) -> Self {
+		let sprites = SpriteSheet::load(texture_dir, "hamster").unwrap();
+		Self::with_sprites(pos, Rc::new(sprites))
+	}
+
+	/// Hamster with given sprite sheet. The frame size determines the bounding box.
+	pub fn with_sprites(pos: Pt, sprites: Rc<SpriteSheet>) -> Self {
+		sprites.check_clips(&Self::CLIPS).expect("hamster sprites");
 		Self {
 			pos,
+			prev_pos: pos,
+			subpix: Pt(0, 0),
 			v_speed_unclamped: 0,
 			jump_state: Falling,
 			h_speed: 0,
 			look_left: false,
-			textures: [
-				Texture::load("hamster3").unwrap(),
-				Texture::load("hamster3").unwrap(), // TODO
-			],
+			physics: Physics::new(),
+			profile: Physics::new(),
+			tick_rate: DEFAULT_TICK_RATE,
+			anim: Animation::new(sprites),
 		}
 	}
 
+	/// Change the movement parameters, e.g. after hot-reloading them.
+	pub fn set_physics(&mut self, physics: Physics) {
+		self.physics = physics.at_tick_rate(self.tick_rate);
+		self.profile = physics;
+	}
+
+	/// Ticks per second the game runs at: the physics are scaled to it.
+	pub fn set_tick_rate(&mut self, tick_rate: u32) {
+		self.tick_rate = tick_rate;
+		self.physics = self.profile.at_tick_rate(tick_rate);
+	}
+
+	/// Shade the sprites with a level's lights.
+	pub fn set_lights(&mut self, lights: &Lights) {
+		let lit = self.anim.sheet().lit(lights);
+		self.anim.set_sheet(Rc::new(lit));
+	}
+
 	pub fn pos(&self) -> Pt {
 		self.pos
 	}
@@ -44,21 +95,83 @@ impl Hamster {
 		self.rect().center()
 	}
 
+	/// Speed in (whole) pixels per tick.
 	pub fn speed(&self) -> Pt {
-		Pt(self.h_speed, self.vertical_delta())
+		Pt(self.h_speed, self.vertical_delta()) / SUBPIX
+	}
+
+	/// Time of death, if dead.
+	pub fn dead_since(&self) -> Option<i32> {
+		match self.jump_state {
+			DeadSince(t) => Some(t),
+			_ => None,
+		}
+	}
+
+	/// Die. The hamster stops moving and ignores keys until respawned.
+	pub fn kill(&mut self, now: i32) {
+		self.jump_state = DeadSince(now);
+		self.h_speed = 0;
+		self.v_speed_unclamped = 0;
+	}
+
+	/// Come back to life at a new position, without any momentum.
+	pub fn respawn(&mut self, pos: Pt) {
+		self.pos = pos;
+		self.prev_pos = pos;
+		self.subpix = Pt(0, 0);
+		self.jump_state = Falling;
+		self.h_speed = 0;
+		self.v_speed_unclamped = 0;
 	}
 
 	// ----------------------------------------------------------------------------- tick
 
 	pub fn tick(&mut self, map: &Map, now: i32, keys: &KeyStates) {
+		self.prev_pos = self.pos;
+		if self.dead_since().is_some() {
+			return;
+		}
+
 		self.update_jump_state(map, now, keys);
 		let dy = self.vertical_delta();
 
-		self.update_walk_state(keys);
+		// briefly ignore the arrow keys after kicking off a wall,
+		// or we would immediately turn back to it.
+		match self.jump_state {
+			WallJumpingSince(t) if now - t < self.physics.wall_kick_ticks => (),
+			_ => self.update_walk_state(map, keys),
+		}
 		let dx = self.horiz_delta();
 
 		self.update_look_dir(keys);
 		self.try_move(map, Pt(dx, dy));
+
+		// squashed, e.g. by a crumbled block re-appearing.
+		if !self.can_move(map, Pt(0, 0)) {
+			self.kill(now);
+		}
+
+		self.update_animation(now);
+	}
+
+	// Pick the animation clip that matches what the hamster is doing.
+	fn update_animation(&mut self, now: i32) {
+		let clip = match self.jump_state {
+			JumpingSince(_) | WallJumpingSince(_) | BouncingSince(_) => "jump",
+			Falling | Dropping | WallSliding(_) => "fall",
+			Landed => "land",
+			Crouching => "crouch",
+			Climbing => "climb",
+			Standing if self.h_speed != 0 => "walk",
+			Standing => "idle",
+			DeadSince(_) => "dead",
+		};
+		// let the landing animation finish, unless we start walking or jumping.
+		if self.anim.clip() == "land" && clip == "idle" && !self.anim.is_done(now) {
+			return;
+		}
+		self.anim.play(clip, now);
 	}
 
 	fn update_look_dir(&mut self, keys: &KeyStates) {
@@ -72,24 +185,23 @@ impl Hamster {
 
 	// ------------------------------------------------------------------------------- walk
 
-	// Maximum horizontal speed (pixels per tick)
-	const WALK_PIX_PER_TICK: i32 = (GRID as i32) / 10;
-	// Horizontal accelleration (pixels per tick per tick);
-	const WALK_ACCEL: i32 = 1;
-	// Coast until aligned with this number of pixels.
-	// Makes aiming for an empty space between blocks easier
-	// and avoids stopping nearly entirely over an edge.
-	const WALK_ALIGN: i32 = (GRID as i32) / 2;
-
-	fn update_walk_state(&mut self, keys: &KeyStates) {
-		let left = keys.is_down(Key::Left);
-		let right = keys.is_down(Key::Right);
-		let keydir = match (left, right) {
-			(false, false) => 0,
-			(true, false) => -1,
-			(false, true) => 1,
-			(true, true) => 0,
-		};
+	fn update_walk_state(&mut self, map: &Map, keys: &KeyStates) {
+		let keydir = Self::key_dir(keys);
+		let max_speed = subpix(if keys.is_down(Key::B) {
+			self.physics.run_pix_per_tick
+		} else {
+			self.physics.walk_pix_per_tick
+		});
+
+		if self.jump_state == Crouching {
+			self.h_speed = 0;
+			return;
+		}
+
+		if self.standing_on(map, BlockTyp::Ice) {
+			self.update_slide_state(keydir, max_speed);
+			return;
+		}
 
 		let currdir = signum(self.h_speed);
 
@@ -101,22 +213,49 @@ impl Hamster {
 
 		// accellerate
 		if keydir != 0 {
-			self.h_speed = clamp(
-				self.h_speed + Self::WALK_ACCEL * keydir,
-				-Self::WALK_PIX_PER_TICK,
-				Self::WALK_PIX_PER_TICK,
-			);
+			let accel = subpix(self.physics.walk_accel);
+			self.h_speed = clamp(self.h_speed + accel * keydir, -max_speed, max_speed);
 		}
 
-		// coast until aligned
+		// coast until aligned, but not beyond.
 		if keydir == 0 {
-			self.h_speed = if self.center().0 % Self::WALK_ALIGN == 0 {
-				0
-			} else if self.center().0 % 2 == 1 {
-				currdir
-			} else {
-				currdir * 2
-			}
+			let dist = self.dist_to_align(currdir);
+			let coast = subpix(self.physics.coast_pix_per_tick);
+			self.h_speed = currdir * min(coast, dist);
+		}
+	}
+
+	// Distance, in sub-pixels, to the next position aligned with walk_align,
+	// in direction dir. 0 if already aligned or dir is 0.
+	fn dist_to_align(&self, dir: i32) -> i32 {
+		let align = self.physics.walk_align * SUBPIX;
+		let x = self.center().0 * SUBPIX + self.subpix.0;
+		let rem = x.rem_euclid(align);
+		match dir {
+			1 if rem != 0 => align - rem,
+			-1 => rem,
+			_ => 0,
+		}
+	}
+
+	// Walking on ice: accellerate, break and stop only slowly.
+	fn update_slide_state(&mut self, keydir: i32, max_speed: i32) {
+		let accel = subpix(self.physics.ice_accel);
+		if keydir == 0 {
+			// slow down, but don't reverse.
+			let currdir = signum(self.h_speed);
+			self.h_speed = currdir * max(abs(self.h_speed) - accel, 0);
+		} else {
+			self.h_speed = clamp(self.h_speed + accel * keydir, -max_speed, max_speed);
+		}
+	}
+
+	// Direction the arrow keys point to: -1 (left), 1 (right) or 0.
+	fn key_dir(keys: &KeyStates) -> i32 {
+		match (keys.is_down(Key::Left), keys.is_down(Key::Right)) {
+			(true, false) => -1,
+			(false, true) => 1,
+			_ => 0,
 		}
 	}
 
@@ -126,28 +265,41 @@ impl Hamster {
 
 	// -------------------------------------------------------------------------------- jump
 
-	const JUMP_MAX_TICKS: i32 = 20; // should be ~3 blocks in ~500 ms
-	const JUMP_MIN_TICKS: i32 = 7; // should be ~1 block
-	const JUMP_PIX_PER_TICK: i32 = (3 * GRID as i32) / Self::JUMP_MAX_TICKS;
-	const JUMP_V_INIT: i32 = (3 * GRID as i32) / Self::JUMP_MAX_TICKS;
-	const JUMP_G: i32 = 2;
-
 	fn update_jump_state(&mut self, map: &Map, now: i32, keys: &KeyStates) {
 		let onfeet = self.onfeet(map);
 		let jumpy = keys.is_down(Key::A);
+		let (up, down) = (keys.is_down(Key::Up), keys.is_down(Key::Down));
+		let climby = (up || down) && self.on_ladder(map);
 
 		match self.jump_state {
 			Standing => {
-				if onfeet && jumpy {
-					self.v_speed_unclamped = -Self::JUMP_V_INIT;
-					self.jump_state = JumpingSince(now);
+				if onfeet && down {
+					self.crouch(map);
+				} else if onfeet && jumpy {
+					self.jump(now);
 				}
 				if !onfeet {
 					self.jump_state = Falling;
 				}
+				if self.standing_on(map, BlockTyp::Bouncy) {
+					self.bounce(now);
+				}
+				if climby && !down {
+					self.jump_state = Climbing;
+				}
 			}
-			JumpingSince(t) => {
-				if now - t > Self::JUMP_MAX_TICKS || (!jumpy && now - t > Self::JUMP_MIN_TICKS) {
+			Crouching => {
+				if !down {
+					self.jump_state = Standing;
+				}
+				if !onfeet {
+					self.jump_state = Falling;
+				}
+			}
+			JumpingSince(t) | WallJumpingSince(t) => {
+				if now - t > self.physics.jump_max_ticks
+					|| (!jumpy && now - t > self.physics.jump_min_ticks)
+				{
 					self.jump_state = Falling;
 				}
 				// bumped into ceiling
@@ -156,12 +308,59 @@ impl Hamster {
 					self.v_speed_unclamped = 0;
 				}
 			}
+			BouncingSince(t) => {
+				if now - t > self.physics.bounce_ticks || !self.can_move(map, Pt(0, -1)) {
+					self.jump_state = Falling;
+					self.v_speed_unclamped = 0;
+				}
+			}
+			Dropping => {
+				// one tick was enough to get past the ledge's top edge.
+				self.jump_state = Falling;
+			}
 			Falling => {
-				self.v_speed_unclamped += Self::JUMP_G;
+				self.v_speed_unclamped += subpix(self.physics.jump_g);
 				if onfeet {
 					self.v_speed_unclamped = 0;
 					self.jump_state = Landed;
 				}
+				if self.standing_on(map, BlockTyp::Bouncy) {
+					self.bounce(now);
+				}
+				if let Some(dir) = self.pushing_wall(map, keys) {
+					if !onfeet && !jumpy && self.v_speed_unclamped > 0 {
+						self.jump_state = WallSliding(dir);
+					}
+				}
+				if climby {
+					self.jump_state = Climbing;
+				}
+			}
+			WallSliding(dir) => {
+				self.v_speed_unclamped = subpix(self.physics.wall_slide_pix_per_tick);
+				if jumpy {
+					self.wall_jump(now, dir);
+				} else if onfeet {
+					self.v_speed_unclamped = 0;
+					self.jump_state = Landed;
+				} else if self.pushing_wall(map, keys) != Some(dir) {
+					self.jump_state = Falling;
+				}
+			}
+			Climbing => {
+				self.v_speed_unclamped = match (up, down) {
+					(true, false) => -subpix(self.physics.climb_pix_per_tick),
+					(false, true) => subpix(self.physics.climb_pix_per_tick),
+					_ => 0,
+				};
+				if jumpy {
+					self.jump(now);
+				} else if !self.on_ladder(map) {
+					self.jump_state = Falling;
+				} else if onfeet && !up {
+					self.v_speed_unclamped = 0;
+					self.jump_state = Standing;
+				}
 			}
 			Landed => {
 				self.v_speed_unclamped = 0;
@@ -172,48 +371,95 @@ impl Hamster {
 					self.jump_state = Falling;
 				}
 			}
+			DeadSince(_) => (),
 		};
 	}
 
+	fn jump(&mut self, now: i32) {
+		self.v_speed_unclamped = -subpix(self.physics.jump_v_init);
+		self.jump_state = JumpingSince(now);
+	}
+
+	// Kick off a wall in direction wall_dir: jump up and away from it.
+	fn wall_jump(&mut self, now: i32, wall_dir: i32) {
+		self.v_speed_unclamped = -subpix(self.physics.jump_v_init);
+		self.h_speed = -wall_dir * subpix(self.physics.walk_pix_per_tick);
+		self.jump_state = WallJumpingSince(now);
+	}
+
+	// Down while standing: drop through a ledge, or crouch on anything else.
+	fn crouch(&mut self, map: &Map) {
+		let floor = self.floor_blocks();
+		let on_ledge = floor.iter().any(|p| map.type_at(*p) == BlockTyp::Ledge);
+		let on_solid = floor.iter().any(|p| map.type_at(*p).is_solid());
+		if on_ledge && !on_solid {
+			self.v_speed_unclamped = subpix(self.physics.jump_g);
+			self.jump_state = Dropping;
+		} else {
+			self.jump_state = Crouching;
+		}
+	}
+
+	fn bounce(&mut self, now: i32) {
+		self.v_speed_unclamped = -subpix(self.physics.jump_v_init);
+		self.jump_state = BouncingSince(now);
+	}
+
 	fn vertical_delta(&self) -> i32 {
 		clamp(
 			self.v_speed_unclamped,
-			-Self::JUMP_PIX_PER_TICK,
-			Self::JUMP_PIX_PER_TICK,
+			-subpix(self.physics.jump_pix_per_tick),
+			subpix(self.physics.jump_pix_per_tick),
 		)
 	}
 
-	// TODO: simplify
+	// Move by delta sub-pixels. Collisions are resolved per whole pixel,
+	// the fractional part is carried over to the next tick
+	// (or dropped when bumping into something).
 	fn try_move(&mut self, map: &Map, delta: Pt) {
-		for _i in 0..abs(delta.0) {
-			self.try_move_partial(&map, Pt(signum(delta.0), 0));
+		let total = self.subpix + delta;
+		let pixels = Pt(total.0.div_euclid(SUBPIX), total.1.div_euclid(SUBPIX));
+		self.subpix = Pt(total.0.rem_euclid(SUBPIX), total.1.rem_euclid(SUBPIX));
+
+		for _i in 0..abs(pixels.0) {
+			if !self.try_move_partial(&map, Pt(signum(pixels.0), 0)) {
+				self.subpix.0 = 0;
+				break;
+			}
 		}
 
-		for _i in 0..abs(delta.1) {
-			self.try_move_partial(&map, Pt(0, signum(delta.1)));
+		for _i in 0..abs(pixels.1) {
+			if !self.try_move_partial(&map, Pt(0, signum(pixels.1))) {
+				self.subpix.1 = 0;
+				break;
+			}
 		}
-
-		assert!(self.can_move(map, Pt(0, 0)));
 	}
 
-	fn try_move_partial(&mut self, map: &Map, dir: Pt) {
-		if self.can_move(&map, dir) {
+	// Move by one pixel if possible, return whether moved.
+	fn try_move_partial(&mut self, map: &Map, dir: Pt) -> bool {
+		let ok = self.can_move(&map, dir);
+		if ok {
 			self.pos += dir;
 		}
+		ok
 	}
 
 	fn can_move(&self, map: &Map, delta: Pt) -> bool {
 		// new bounding box after move.
 		let newrect = self.rect().transl(delta);
 
-		// cannot move into a brick
+		// cannot move into a brick (or any other solid block)
 		for vertex in &newrect.vertices_incl() {
-			if map.type_at(*vertex / GRID) == BlockTyp::Brick {
+			if map.type_at(*vertex / GRID).is_solid() {
 				return false;
 			}
 		}
 
-		// can jump onto a ledge, but not fall trough
+		// can jump onto a ledge, but not fall trough (unless dropping on purpose)
+		if self.jump_state == Dropping {
+			return true;
+		}
 		let oldvert = self.rect().vertices_bottom();
 		for (i, newvert) in newrect.vertices_bottom().iter().enumerate() {
 			let oldy = oldvert[i].1 / (GRID as i32);
@@ -228,29 +474,355 @@ impl Hamster {
 		true
 	}
 
-	fn onfeet(&self, map: &Map) -> bool {
+	pub fn onfeet(&self, map: &Map) -> bool {
 		!self.can_move(map, Pt(0, 1))
 	}
 
-	fn rect(&self) -> Rect {
+	/// Grid positions of the (up to 2) blocks right below the hamster's feet.
+	pub fn floor_blocks(&self) -> [Pt; 2] {
+		let [a, b] = self.rect().vertices_bottom();
+		[(a + (0, 1)) / GRID, (b + (0, 1)) / GRID]
+	}
+
+	/// Is the hamster standing on a block of given type?
+	fn standing_on(&self, map: &Map, typ: BlockTyp) -> bool {
+		self.onfeet(map) && self.floor_blocks().iter().any(|p| map.type_at(*p) == typ)
+	}
+
+	/// Is the hamster's center on a ladder?
+	fn on_ladder(&self, map: &Map) -> bool {
+		map.type_at(self.center() / GRID) == BlockTyp::Ladder
+	}
+
+	/// Direction (-1 or 1) of the brick wall the hamster is pushing against, if any.
+	fn pushing_wall(&self, map: &Map, keys: &KeyStates) -> Option<i32> {
+		let dir = Self::key_dir(keys);
+		if dir == 0 || self.can_move(map, Pt(dir, 0)) {
+			return None;
+		}
+		let v = self.rect().transl(Pt(dir, 0)).vertices_incl();
+		let side = if dir < 0 { [v[0], v[3]] } else { [v[1], v[2]] };
+		if side
+			.iter()
+			.any(|p| map.type_at(*p / GRID) == BlockTyp::Brick)
+		{
+			Some(dir)
+		} else {
+			None
+		}
+	}
+
+	/// Is the hamster overlapping, or directly adjacent to, a deadly block (spikes, lava)?
+	/// Only blocks sharing an edge count, touching a corner is safe.
+	pub fn touches_deadly(&self, map: &Map) -> bool {
+		let r = self.rect();
+		let horiz = Rect {
+			min: r.min - (1, 0),
+			max: r.max + (1, 0),
+		};
+		let vert = Rect {
+			min: r.min - (0, 1),
+			max: r.max + (0, 1),
+		};
+		horiz
+			.vertices_incl()
+			.iter()
+			.chain(vert.vertices_incl().iter())
+			.any(|v| map.type_at(*v / GRID).is_deadly())
+	}
+
+	pub fn rect(&self) -> Rect {
 		//let margin = 4; // TODO: Rect::shrink(margin)
-		Rect::new(self.pos, self.textures[0].dimensions())
+		Rect::new(self.pos, self.anim.sheet().frame_dimensions())
+	}
+
+	/// Draw at a fraction alpha (0..1) of the way between the previous and current tick.
+	pub fn draw(&self, disp: &mut Viewport, time: i32, alpha: f64) {
+		if let Some(t) = self.dead_since() {
+			self.draw_dying(disp, time, time - t);
+			return;
+		}
+		let pos = interpolate(self.prev_pos, self.pos, alpha);
+		if let Some(shadow) = self.anim.shadow(time) {
+			let offset = self.anim.sheet().shadow_offset();
+			disp.draw_texture(shadow, pos + offset, self.look_left);
+		}
+		disp.draw_texture(self.anim.frame(time), pos, self.look_left);
 	}
 
-	pub fn draw(&self, disp: &mut Viewport, time: i32) {
-		let i = if time % 16 > 7 { 1 } else { 0 };
-		disp.draw_texture(&self.textures[i], self.pos, self.look_left);
+	// death animation: float upwards while blinking.
+	fn draw_dying(&self, disp: &mut Viewport, time: i32, ticks: i32) {
+		if ticks % 8 > 3 {
+			return;
+		}
+		let pos = self.pos - Pt(0, 2 * ticks);
+		disp.draw_texture(self.anim.frame(time), pos, self.look_left);
 	}
 
 	// ----------------------------------------------------------------------------------- debug
 
-	pub fn print_stats(&self) {
-		println!("hamster: pos={}, center={}", self.pos(), self.center());
-		println!("         rect={:?}", self.rect());
-		println!("         h_speed={}", self.h_speed);
-		println!(
-			"         jump_state={:?}, v_speed_unclamped={}",
-			self.jump_state, self.v_speed_unclamped
+	/// State for the debug overlay.
+	pub fn debug_lines(&self) -> Vec<String> {
+		vec![
+			format!("hamster: pos={}, center={}", self.pos(), self.center()),
+			format!("         subpix={}, h_speed={}", self.subpix, self.h_speed),
+			format!(
+				"         jump_state={:?}, v_speed_unclamped={}",
+				self.jump_state, self.v_speed_unclamped
+			),
+		]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Map from ASCII art: '#' brick, '-' ledge, 'H' ladder, anything else empty.
+	// Note that rows 0, 1 and columns 0, 1 are always out of bounds (i.e. brick).
+	fn test_map(rows: &[&str]) -> Map {
+		let mut bytes = ByteMap::new();
+		for (y, row) in rows.iter().enumerate() {
+			for (x, c) in row.chars().enumerate() {
+				let blk = match c {
+					'#' => 1,
+					'-' => 3,
+					'H' => 35,
+					_ => 0,
+				};
+				bytes.set(Pt(x as i32, y as i32), blk);
+			}
+		}
+		let palette = (0..ED_PALETTE.len()).map(|_| Surface::default()).collect();
+		Map::with_palette(
+			bytes,
+			FnvHashMap::default(),
+			Lights::new(),
+			palette,
+			RenderOptions::default(),
+		)
+	}
+
+	// Hamster with a 32x32 pixel bounding box.
+	fn test_hamster(pos: Pt) -> Hamster {
+		Hamster::with_sprites(pos, Rc::new(SpriteSheet::still(Image::new((32, 32)))))
+	}
+
+	// Advance the hamster a number of ticks, while holding down keys.
+	fn run(h: &mut Hamster, map: &Map, now: &mut i32, ticks: i32, keys: &[Key]) {
+		let mut debouncer = KeyDebouncer::new();
+		for k in keys {
+			debouncer.key_down(*k);
+		}
+		for _ in 0..ticks {
+			*now += 1;
+			h.tick(map, *now, &debouncer.key_states());
+		}
+	}
+
+	// top-left position of a 32x32 hamster standing on the floor of grid cell (x, y).
+	fn standing_in(x: i32, y: i32) -> Pt {
+		Pt(x * GRID as i32 + 16, (y + 1) * GRID as i32 - 32)
+	}
+
+	#[test]
+	fn test_sprite_clips() {
+		// the shipped sheet has every clip the hamster plays.
+		// (its PNG is built by the assets binary, an empty image of the same layout will do).
+		let desc = SheetDesc::load(Path::new("assets/textures/hamster.anim.json")).unwrap();
+		let (cols, rows) = desc.grid;
+		let sheet = SpriteSheet::new(Image::new((cols * 64, rows * 35)), desc).unwrap();
+		sheet.check_clips(&Hamster::CLIPS).unwrap();
+	}
+
+	#[test]
+	fn test_run() {
+		let map = test_map(&["", "", "", "", "####################"]);
+		let mut now = 0;
+
+		let mut walker = test_hamster(standing_in(3, 3));
+		run(&mut walker, &map, &mut now, 40, &[Key::Right]);
+
+		let mut runner = test_hamster(standing_in(3, 3));
+		run(&mut runner, &map, &mut now, 40, &[Key::Right, Key::B]);
+
+		assert!(walker.pos().x() > standing_in(3, 3).x());
+		assert!(runner.pos().x() > walker.pos().x());
+		assert_eq!(runner.speed().x(), Physics::new().run_pix_per_tick as i32);
+	}
+
+	#[test]
+	fn test_tick_rate() {
+		// a second of running and jumping ends up in about the same place at any tick rate.
+		let map = test_map(&["", "", "", "", "", "", "####################"]);
+		let play = |tick_rate: i32| {
+			let mut h = test_hamster(standing_in(3, 5));
+			h.set_tick_rate(tick_rate as u32);
+			let mut now = 0;
+			run(&mut h, &map, &mut now, tick_rate / 4, &[Key::Right]);
+			run(&mut h, &map, &mut now, tick_rate / 4, &[Key::Right, Key::A]);
+			let jumping = h.pos();
+			run(&mut h, &map, &mut now, tick_rate / 2, &[Key::Right]);
+			(jumping, h.pos())
+		};
+		let (j60, p60) = play(60);
+		let (j120, p120) = play(120);
+		assert!(p60.x() > standing_in(5, 5).x(), "{}", p60);
+		assert!(j60.y() < standing_in(3, 5).y(), "{}", j60);
+		let close = |a: Pt, b: Pt| (a.x() - b.x()).abs() <= 8 && (a.y() - b.y()).abs() <= 8;
+		assert!(close(j60, j120), "{} {}", j60, j120);
+		assert!(close(p60, p120), "{} {}", p60, p120);
+	}
+
+	#[test]
+	fn test_crouch() {
+		let map = test_map(&["", "", "", "", "##########"]);
+		let mut now = 0;
+		let start = standing_in(3, 3);
+		let mut h = test_hamster(start);
+
+		run(&mut h, &map, &mut now, 5, &[]);
+		assert_eq!(h.jump_state, Standing);
+
+		// cannot walk or fall while crouching on a brick.
+		run(&mut h, &map, &mut now, 20, &[Key::Down, Key::Right]);
+		assert_eq!(h.jump_state, Crouching);
+		assert_eq!(h.pos(), start);
+
+		run(&mut h, &map, &mut now, 1, &[]);
+		assert_eq!(h.jump_state, Standing);
+	}
+
+	#[test]
+	fn test_drop_through_ledge() {
+		let map = test_map(&["", "", "", "", "----------", "", "", "##########"]);
+		let mut now = 0;
+		let mut h = test_hamster(standing_in(3, 3));
+
+		// does not fall through by itself
+		run(&mut h, &map, &mut now, 20, &[]);
+		assert_eq!(h.pos(), standing_in(3, 3));
+
+		// but does so when pressing down
+		run(&mut h, &map, &mut now, 1, &[Key::Down]);
+		run(&mut h, &map, &mut now, 40, &[]);
+		assert_eq!(h.pos(), standing_in(3, 6));
+	}
+
+	#[test]
+	fn test_climb_ladder() {
+		let map = test_map(&[
+			"",
+			"",
+			"....H",
+			"....H",
+			"....H",
+			"....H",
+			"....H",
+			"##########",
+		]);
+		let mut now = 0;
+		let start = standing_in(4, 6);
+		let mut h = test_hamster(start);
+		run(&mut h, &map, &mut now, 5, &[]);
+		assert_eq!(h.pos(), start);
+
+		run(&mut h, &map, &mut now, 30, &[Key::Up]);
+		assert_eq!(h.jump_state, Climbing);
+		assert!(h.pos().y() < start.y() - GRID as i32);
+
+		// hang on without falling
+		let pos = h.pos();
+		run(&mut h, &map, &mut now, 10, &[]);
+		assert_eq!(h.pos(), pos);
+
+		// climb back down
+		run(&mut h, &map, &mut now, 60, &[Key::Down]);
+		assert_eq!(h.pos(), start);
+	}
+
+	#[test]
+	fn test_wall_slide_and_jump() {
+		let rows = [
+			"",
+			"",
+			"......#",
+			"......#",
+			"......#",
+			"......#",
+			"......#",
+			"......#",
+			"##########",
+		];
+		let map = test_map(&rows);
+		let mut now = 0;
+		let start = Pt(6 * GRID as i32 - 32, 2 * GRID as i32 + 10);
+		let mut h = test_hamster(start);
+
+		// pushing against the wall while falling: slide down slowly.
+		run(&mut h, &map, &mut now, 10, &[Key::Right]);
+		assert_eq!(h.jump_state, WallSliding(1));
+		let y = h.pos().y();
+		run(&mut h, &map, &mut now, 10, &[Key::Right]);
+		assert_eq!(
+			h.pos().y(),
+			y + 10 * Physics::new().wall_slide_pix_per_tick as i32
 		);
+
+		// kick off the wall, even while still pushing towards it.
+		let pos = h.pos();
+		run(&mut h, &map, &mut now, 5, &[Key::Right, Key::A]);
+		assert!(h.pos().x() < pos.x());
+		assert!(h.pos().y() < pos.y());
+	}
+
+	#[test]
+	fn test_subpixel_walk() {
+		let map = test_map(&["", "", "", "", "####################"]);
+		let mut now = 0;
+		let start = standing_in(3, 3);
+		let mut h = test_hamster(start);
+		h.set_physics(Physics {
+			walk_pix_per_tick: 0.5,
+			walk_accel: 0.5,
+			..Physics::new()
+		});
+		run(&mut h, &map, &mut now, 5, &[]);
+
+		run(&mut h, &map, &mut now, 20, &[Key::Right]);
+		assert_eq!(h.pos(), start + (10, 0));
+		assert_eq!(h.speed(), Pt(0, 0)); // less than a pixel per tick
+	}
+
+	#[test]
+	fn test_deterministic() {
+		let map = test_map(&["", "", "", "..........-", "", "", "####################"]);
+		let physics = Physics {
+			jump_g: 0.7,
+			walk_accel: 0.3,
+			..Physics::new()
+		};
+		let script: &[(i32, &[Key])] = &[
+			(10, &[Key::Right]),
+			(15, &[Key::Right, Key::A]),
+			(20, &[Key::Left, Key::B]),
+			(30, &[]),
+		];
+
+		let replay = || {
+			let mut h = test_hamster(standing_in(3, 5));
+			h.set_physics(physics.clone());
+			let mut now = 0;
+			let mut trace = Vec::new();
+			for (ticks, keys) in script {
+				for _ in 0..*ticks {
+					run(&mut h, &map, &mut now, 1, keys);
+					trace.push((h.pos(), h.subpix, h.h_speed, h.v_speed_unclamped));
+				}
+			}
+			trace
+		};
+
+		assert_eq!(replay(), replay());
 	}
 }
@@ -1,6 +1,8 @@
-/// Logical key codes, after being mapped from physical keys (by fn keymap).
+use serde::{Deserialize, Serialize};
+
+/// Logical key codes, after being mapped from physical keys and buttons (by Bindings).
 #[repr(u8)]
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Key {
 	None = 0,
 	Left = 1,
@@ -17,6 +19,7 @@ pub enum Key {
 	PrevMap = 12,
 	Restart = 13,
 	NextMap = 14,
+	Debug = 15,
 }
 
 /// KeyStates records which of the lowest 8 Keys are currently pressed down.
@@ -11,6 +11,12 @@ pub struct LevelData {
 	pub map_bytes: ByteMap,
 	pub goodies: Vec<(Pt, u8)>,
 	pub lights: Lights,
+	/// Level-specific physics (e.g. low gravity), layered on physics_file().
+	#[serde(default)]
+	pub physics: Option<PhysicsOverride>,
+	/// Put goodies collected since the latest checkpoint back when the hamster dies.
+	#[serde(default)]
+	pub reset_goodies: bool,
 }
 
 // TODO: embed in editor?
@@ -22,12 +28,21 @@ impl LevelData {
 			map_bytes: ByteMap::new(),
 			lights: Lights::new(),
 			goodies: Vec::new(),
+			physics: None,
+			reset_goodies: false,
 		}
 	}
 
 	/// Save as JSON.
-	pub fn save(p: &Path, map: &ByteMap, goodies: &FnvHashMap<Pt, u8>, l: &Lights) -> Result<()> {
-		let data = Self::from(map, goodies, l);
+	pub fn save(
+		p: &Path,
+		map: &ByteMap,
+		goodies: &FnvHashMap<Pt, u8>,
+		l: &Lights,
+		physics: &Option<PhysicsOverride>,
+		reset_goodies: bool,
+	) -> Result<()> {
+		let data = Self::from(map, goodies, l, physics, reset_goodies);
 		let f = File::create(p)?;
 		let mut b = BufWriter::new(f);
 		serde_json::to_writer(&mut b, &data)?;
@@ -43,14 +58,62 @@ impl LevelData {
 		let b = BufReader::new(f);
 		let mut data: Self = serde_json::from_reader(b)?;
 		data.lights.sun_dir.normalize(); // in case it got hand-edited
+		data.lights.validate()?;
 		Ok(data)
 	}
 
-	fn from(map_bytes: &ByteMap, goodies: &FnvHashMap<Pt, u8>, lights: &Lights) -> Self {
+	/// Check for problems that would break the game or the editor,
+	/// returning a human-readable message for each.
+	pub fn validate(&self) -> Vec<String> {
+		let mut problems = Vec::new();
+		let types = block_types();
+		let known = |b: u8| ED_PALETTE.iter().any(|def| def.uid == b);
+
+		for (y, row) in self.map_bytes.blocks.iter().enumerate() {
+			for (x, &b) in row.iter().enumerate() {
+				if !known(b) {
+					problems.push(format!("block ({}, {}): unknown block {}", x, y, b));
+				} else if types[b as usize] == BlockTyp::Goody {
+					problems.push(format!(
+						"block ({}, {}): goody {} stored as a block",
+						x, y, b
+					));
+				}
+			}
+		}
+
+		let mut seen = FnvHashSet::default();
+		for &(p, g) in &self.goodies {
+			if !known(g) || types[g as usize] != BlockTyp::Goody {
+				problems.push(format!("goody {:?}: {} is not a goody", p, g));
+			}
+			if p.0 < 0 || p.1 < 0 {
+				problems.push(format!("goody {:?}: negative position", p));
+			}
+			if !seen.insert(p) {
+				problems.push(format!("goody {:?}: duplicate", p));
+			}
+		}
+
+		if self.lights.sun_rays == 0 && self.lights.ambient_rays == 0 {
+			problems.push("lights: no sun or ambient rays".into());
+		}
+		problems
+	}
+
+	fn from(
+		map_bytes: &ByteMap,
+		goodies: &FnvHashMap<Pt, u8>,
+		lights: &Lights,
+		physics: &Option<PhysicsOverride>,
+		reset_goodies: bool,
+	) -> Self {
 		Self {
 			map_bytes: map_bytes.clone(), // TODO: don't clone
 			lights: lights.clone(),
 			goodies: Self::map_to_vec(goodies),
+			physics: physics.clone(),
+			reset_goodies,
 		}
 	}
 
@@ -64,3 +127,27 @@ impl LevelData {
 		map.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
 	}
 }
+
+#[test]
+fn test_validate() {
+	let mut data = LevelData::new();
+	assert!(data.validate().is_empty());
+
+	data.map_bytes.set(Pt(2, 3), 2);
+	data.goodies.push((Pt(4, 4), 28));
+	assert!(data.validate().is_empty());
+
+	data.map_bytes.set(Pt(5, 5), 200);
+	data.map_bytes.set(Pt(6, 5), 28);
+	data.goodies.push((Pt(4, 4), 28));
+	data.goodies.push((Pt(7, 7), 2));
+	assert_eq!(
+		data.validate(),
+		vec![
+			"block (5, 5): unknown block 200",
+			"block (6, 5): goody 28 stored as a block",
+			"goody Pt(4, 4): duplicate",
+			"goody Pt(7, 7): 2 is not a goody",
+		]
+	);
+}
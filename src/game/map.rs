@@ -6,38 +6,66 @@ use serde::{Deserialize, Serialize};
 pub struct Map {
 	inner: ByteMap, // maps position -> byte. TODO: rename "blocks"
 	goodies: FnvHashMap<Pt, u8>,
-	renderer: RefCell<Renderer>, // maps byte -> texture
+	renderer: Rc<RefCell<Renderer>>, // maps byte -> texture, shared by clones
 	block_types: Vec<BlockTyp>,
 }
 
 impl Map {
 	/// Construct an empty Map.
-	pub fn new() -> Self {
+	pub fn new(config: &Config) -> Result<Self> {
 		Self::from(
 			ByteMap::new(),
 			FnvHashMap::<Pt, u8>::default(),
 			Lights::new(),
+			config,
 		)
 	}
 
 	/// Construct a Map from a sparse 2D byte array representing the blocks.
-	/// Used during deserialization.
-	pub fn from(bytes: ByteMap, goodies: FnvHashMap<Pt, u8>, lights: Lights) -> Self {
+	/// Used during deserialization. The palette is loaded from config.texture_dir.
+	pub fn from(
+		bytes: ByteMap,
+		goodies: FnvHashMap<Pt, u8>,
+		lights: Lights,
+		config: &Config,
+	) -> Result<Self> {
+		let palette = load_palette(&config.texture_dir)?;
+		Ok(Self::with_palette(
+			bytes,
+			goodies,
+			lights,
+			palette,
+			config.render,
+		))
+	}
+
+	/// Like from, but with explicit surfaces instead of the palette loaded from disk.
+	/// The palette is indexed by block uid, like ED_PALETTE.
+	pub fn with_palette(
+		bytes: ByteMap,
+		goodies: FnvHashMap<Pt, u8>,
+		lights: Lights,
+		palette: Vec<Surface>,
+		opts: RenderOptions,
+	) -> Self {
+		let renderer = Renderer::with_options(palette, lights, opts);
 		Self {
 			inner: bytes,
 			goodies,
-			renderer: RefCell::new(Renderer::new(default_palette(), lights)),
+			renderer: Rc::new(RefCell::new(renderer)),
 			block_types: block_types(),
 		}
 	}
 
-	// TODO: remove!
+	/// Copy of the blocks and goodies, sharing the renderer
+	/// (so that baked tiles are not thrown away, e.g. when playing a level from the editor).
 	pub fn clone(&self) -> Self {
-		Self::from(
-			self.inner.clone(),
-			self.goodies.clone(),
-			self.renderer.borrow().lights(),
-		)
+		Self {
+			inner: self.inner.clone(),
+			goodies: self.goodies.clone(),
+			renderer: self.renderer.clone(),
+			block_types: self.block_types.clone(),
+		}
 	}
 
 	pub fn bytemap(&self) -> &ByteMap {
@@ -52,6 +80,17 @@ impl Map {
 		self.renderer.borrow().lights()
 	}
 
+	/// Bake with other lights from now on (e.g. edited in the level file).
+	pub fn set_lights(&mut self, lights: Lights) {
+		self.renderer.borrow_mut().set_lights(lights)
+	}
+
+	/// Replace the surface of block uid (e.g. after its textures changed on disk),
+	/// re-baking only the tiles that depend on it (see Renderer::set_surface).
+	pub fn set_surface(&mut self, uid: u8, surface: Surface) {
+		self.renderer.borrow_mut().set_surface(uid, surface);
+	}
+
 	// Start caching replacement tiles for every block in this map.
 	// When the map is actually being rendered, replacement tiles will be available more rapidly.
 	pub fn warmup_cache(&self) {
@@ -71,6 +110,13 @@ impl Map {
 		renderer.render_tile(self.tile_key(p))
 	}
 
+	/// Like texture_at, but bake the tile before all others if it is not baked yet
+	/// (e.g. the tile a block was just drawn on in the editor).
+	pub fn texture_at_urgent(&self, p: Pt) -> Rc<Texture> {
+		let mut renderer = self.renderer.borrow_mut();
+		renderer.render_tile_urgent(self.tile_key(p))
+	}
+
 	pub fn type_at(&self, p: Pt) -> BlockTyp {
 		self.type_of(self.at(p))
 	}
@@ -114,15 +160,11 @@ impl Map {
 	}
 
 	fn tile_key(&self, grid: Pt) -> TileKey {
-		let (ix, iy) = (grid.0, grid.1);
+		let r = TILE_KEY_RADIUS as i32;
 		let mut k = TileKey::new();
-		for cy in 0..3 {
-			for cx in 0..3 {
-				let mx = ix + cx - 1;
-				let my = iy + cy - 1;
-				let cx = cx as usize;
-				let cy = cy as usize;
-				k.blocks[cy][cx] = self.at(Pt(mx, my));
+		for dy in -r..=r {
+			for dx in -r..=r {
+				k.set(dx, dy, self.at(grid + Pt(dx, dy)));
 			}
 		}
 
@@ -133,7 +175,72 @@ impl Map {
 		k
 	}
 
-	// TODO: use
+	/// Size of the level in blocks: the bounding box of all non-empty blocks and goodies,
+	/// starting from the origin.
+	pub fn dimensions(&self) -> (i32, i32) {
+		let mut dim = (0, 0);
+		for (y, row) in self.inner.blocks.iter().enumerate() {
+			if let Some(x) = row.iter().rposition(|&b| b != 0) {
+				dim.0 = max(dim.0, x as i32 + 1);
+				dim.1 = y as i32 + 1;
+			}
+		}
+		for p in self.goodies.keys() {
+			dim.0 = max(dim.0, p.0 + 1);
+			dim.1 = max(dim.1, p.1 + 1);
+		}
+		dim
+	}
+
+	/// Ray-trace the entire level into a single image, waiting for all tiles to be baked.
+	/// Empty tiles are left transparent.
+	pub fn render_level(&self) -> Image<BGRA> {
+		let (w, h) = self.dimensions();
+		let g = GRID as i32;
+		let mut img = Image::<BGRA>::new((w * g, h * g));
+		for (p, _, tile) in self.bake_tiles() {
+			img.paste((p * GRID).as_tuple(), tile.image());
+		}
+		img
+	}
+
+	/// Ray-trace all distinct tiles in the level, packed into an Atlas.
+	pub fn render_atlas(&self) -> Atlas {
+		let tiles = self.bake_tiles();
+		let tiles: Vec<(TileKey, &Image<BGRA>)> =
+			tiles.iter().map(|(_, k, t)| (*k, t.image())).collect();
+		Atlas::pack(&tiles)
+	}
+
+	// Bake every non-empty tile (a block or goody in the center),
+	// blocking until done. Returns grid position, tile key and texture for each.
+	fn bake_tiles(&self) -> Vec<(Pt, TileKey, Rc<Texture>)> {
+		let (w, h) = self.dimensions();
+		let mut grids = Vec::new();
+		let mut keys = Vec::new();
+		for iy in 0..h {
+			for ix in 0..w {
+				let k = self.tile_key(Pt(ix, iy));
+				if k.center() != 0 || k.goody != 0 {
+					grids.push(Pt(ix, iy));
+					keys.push(k);
+				}
+			}
+		}
+		let tiles = self.renderer.borrow_mut().render_tiles_blocking(&keys);
+		grids
+			.into_iter()
+			.zip(keys)
+			.zip(tiles)
+			.map(|((p, k), t)| (p, k, t))
+			.collect()
+	}
+
+	/// Renderer statistics, shown in the debug overlay.
+	pub fn render_stats(&self) -> RenderStats {
+		self.renderer.borrow().stats()
+	}
+
 	pub fn print_stats(&self) {
 		self.renderer.borrow().print_stats();
 	}
@@ -250,3 +357,38 @@ impl ByteMap {
 //		Ok(())
 //	}
 //}
+
+#[test]
+fn test_render_level() {
+	let g = GRID as i32;
+	let opaque = || {
+		Surface::new(
+			Image::new((g, g)),
+			Image::from_fn((g, g), |_, _| BGRA(9, 9, 9, 255)),
+		)
+	};
+	let mut palette: Vec<Surface> = (0..ED_PALETTE.len()).map(|_| opaque()).collect();
+	palette[0] = Surface::new(Image::new((g, g)), Image::new((g, g)));
+	let mut lights = Lights::new();
+	lights.sun_rays = 1;
+	lights.ambient_rays = 1;
+
+	let mut bytes = ByteMap::new();
+	bytes.set(Pt(3, 2), 2);
+	let mut goodies = FnvHashMap::default();
+	goodies.insert(Pt(2, 3), 28);
+	let map = Map::with_palette(bytes, goodies, lights, palette, RenderOptions::default());
+	assert_eq!(map.dimensions(), (4, 4));
+
+	let img = map.render_level();
+	assert_eq!(img.dimensions(), (4 * g, 4 * g));
+	let center = |x: i32, y: i32| img.at((x * g + g / 2, y * g + g / 2));
+	assert_eq!(center(3, 2).a(), 255); // block
+	assert_eq!(center(3, 3), BGRA(0, 0, 0, 0)); // empty, not rendered
+	assert_eq!(center(0, 0).a(), 255); // out of bounds brick
+
+	let atlas = map.render_atlas();
+	assert!(atlas.rect(map.tile_key(Pt(3, 2))).is_some());
+	assert!(atlas.rect(map.tile_key(Pt(2, 3))).is_some());
+	assert!(atlas.rect(map.tile_key(Pt(3, 3))).is_none());
+}
@@ -0,0 +1,82 @@
+use crate::prelude::*;
+
+/// A vertical list of items, navigated with Up/Down and chosen with A.
+pub struct Menu {
+	title: String,
+	items: Vec<String>,
+	selected: usize,
+}
+
+impl Menu {
+	pub fn new(title: &str, items: &[&str]) -> Self {
+		Self::with_items(title, items.iter().map(|s| s.to_string()).collect())
+	}
+
+	pub fn with_items(title: &str, items: Vec<String>) -> Self {
+		Self {
+			title: title.into(),
+			items,
+			selected: 0,
+		}
+	}
+
+	pub fn selected(&self) -> usize {
+		self.selected
+	}
+
+	/// Handle a key press. Returns the index of the item chosen with Key::A, if any.
+	pub fn key_down(&mut self, k: Key) -> Option<usize> {
+		let n = self.items.len();
+		if n == 0 {
+			return None;
+		}
+		match k {
+			Key::Up => self.selected = (self.selected + n - 1) % n,
+			Key::Down => self.selected = (self.selected + 1) % n,
+			Key::A => return Some(self.selected),
+			_ => (),
+		}
+		None
+	}
+
+	const TITLE_SCALE: i32 = 6;
+	const ITEM_SCALE: i32 = 3;
+
+	/// Draw the title and items, centered on the screen.
+	pub fn draw(&self, disp: &mut SDLDisplay, font: &Font) {
+		let (w, h) = disp.dimensions();
+		let (tw, th) = Font::text_dimensions(&self.title, Self::TITLE_SCALE);
+		let items = self.text();
+		let (iw, ih) = Font::text_dimensions(&items, Self::ITEM_SCALE);
+
+		let top = (h - th - ih) / 2;
+		disp.draw_text(font, &self.title, Pt((w - tw) / 2, top), Self::TITLE_SCALE);
+		disp.draw_text(font, &items, Pt((w - iw) / 2, top + th), Self::ITEM_SCALE);
+	}
+
+	/// Items, one per line, with a cursor before the selected one.
+	pub fn text(&self) -> String {
+		let mut text = String::new();
+		for (i, item) in self.items.iter().enumerate() {
+			text.push_str(if i == self.selected { "> " } else { "  " });
+			text.push_str(item);
+			text.push('\n');
+		}
+		text
+	}
+}
+
+#[test]
+fn test_menu() {
+	let mut m = Menu::new("TITLE", &["PLAY", "QUIT"]);
+	assert_eq!(m.key_down(Key::Up), None);
+	assert_eq!(m.selected(), 1); // wraps around
+	assert_eq!(m.key_down(Key::Down), None);
+	assert_eq!(m.selected(), 0);
+	assert_eq!(m.key_down(Key::Left), None);
+	assert_eq!(m.key_down(Key::A), Some(0));
+	assert_eq!(m.text(), "> PLAY\n  QUIT\n");
+
+	let mut empty = Menu::with_items("EMPTY", Vec::new());
+	assert_eq!(empty.key_down(Key::A), None);
+}
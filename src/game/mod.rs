@@ -1,9 +1,18 @@
+pub mod animation;
+pub mod app;
+pub mod bindings;
+pub mod cli;
+pub mod config;
+pub mod font;
+pub mod frontend;
 pub mod gamestate;
 pub mod hamster;
 pub mod key;
 pub mod leveldata;
 pub mod map;
+pub mod menu;
 pub mod palette;
+pub mod physics;
 pub mod prelude;
 pub mod sdl_interface;
 pub mod texture;
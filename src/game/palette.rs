@@ -1,7 +1,8 @@
 use crate::prelude::*;
+use std::time::{Duration, Instant};
 
 /// Block definitions in the order as they will appear in the editor.
-pub const ED_PALETTE: [BlockDef; 29] = [
+pub const ED_PALETTE: [BlockDef; 36] = [
 	BlockDef {
 		uid: 0, // empty
 		srf: "",
@@ -12,6 +13,11 @@ pub const ED_PALETTE: [BlockDef; 29] = [
 		srf: "seed",
 		walk: Goody,
 	},
+	BlockDef {
+		uid: 34,
+		srf: "checkpoint",
+		walk: Checkpoint,
+	},
 	// hydrogen
 	BlockDef {
 		uid: 17,
@@ -38,6 +44,11 @@ pub const ED_PALETTE: [BlockDef; 29] = [
 		srf: "hydrogen-ledge",
 		walk: Ledge,
 	},
+	BlockDef {
+		uid: 35,
+		srf: "ladder",
+		walk: Ladder,
+	},
 	BlockDef {
 		uid: 26,
 		srf: "hydrogen-brick-deep",
@@ -154,21 +165,77 @@ pub const ED_PALETTE: [BlockDef; 29] = [
 		srf: "silicon-top",
 		walk: Brick,
 	},
+	// hazards
+	BlockDef {
+		uid: 29,
+		srf: "spikes",
+		walk: Spikes,
+	},
+	BlockDef {
+		uid: 30,
+		srf: "lava",
+		walk: Lava,
+	},
+	BlockDef {
+		uid: 31,
+		srf: "crumble",
+		walk: Crumbling,
+	},
+	BlockDef {
+		uid: 32,
+		srf: "bouncy",
+		walk: Bouncy,
+	},
+	BlockDef {
+		uid: 33,
+		srf: "ice",
+		walk: Ice,
+	},
 ];
 
 #[derive(Copy, Clone, Default)]
 pub struct BlockDef {
 	pub uid: u8,
 	pub walk: BlockTyp,
-	srf: &'static str,
+	pub srf: &'static str,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum BlockTyp {
 	Wall,
 	Ledge,
 	Brick,
 	Goody,
+	/// Solid, kills the hamster on contact.
+	Spikes,
+	/// Not solid, kills the hamster on contact.
+	Lava,
+	/// Solid, but collapses some time after being stood on, and respawns later.
+	Crumbling,
+	/// Solid, launches the hamster upwards when landed on.
+	Bouncy,
+	/// Solid, but slippery: reduced walking friction.
+	Ice,
+	/// Not solid, records the respawn position when touched.
+	Checkpoint,
+	/// Not solid, can be climbed with Up/Down.
+	Ladder,
+}
+
+impl BlockTyp {
+	/// Can the hamster not move into this block?
+	/// (Ledges are special: they are only solid when falling onto them).
+	pub fn is_solid(self) -> bool {
+		match self {
+			Brick | Spikes | Crumbling | Bouncy | Ice => true,
+			Wall | Ledge | Goody | Lava | Checkpoint | Ladder => false,
+		}
+	}
+
+	/// Does touching this block kill the hamster?
+	pub fn is_deadly(self) -> bool {
+		matches!(self, Spikes | Lava)
+	}
 }
 
 impl Default for BlockTyp {
@@ -187,11 +254,16 @@ pub fn block_types() -> Vec<BlockTyp> {
 	s
 }
 
-pub fn default_palette() -> Vec<Surface> {
-	load_palette(&texture_dir()).unwrap()
+/// Load the surface of one block from texture_dir, e.g. after its textures changed.
+pub fn load_surface(texture_dir: &Path, uid: u8) -> Result<Surface> {
+	match ED_PALETTE.iter().find(|def| def.uid == uid) {
+		Some(def) if uid != 0 => Surface::load(&texture_dir.join(def.srf)),
+		_ => GenError::new(format!("no textures for block {}", uid)),
+	}
 }
 
-fn load_palette(texture_dir: &Path) -> Result<Vec<Surface>> {
+/// Load the surfaces of all blocks from texture_dir, indexed by block uid.
+pub fn load_palette(texture_dir: &Path) -> Result<Vec<Surface>> {
 	let mut s = zero_vec(ED_PALETTE.len());
 
 	// surface 0 is fully transparent.
@@ -205,6 +277,50 @@ fn load_palette(texture_dir: &Path) -> Result<Vec<Surface>> {
 	Ok(s)
 }
 
+/// Polls the textures of all blocks for changes,
+/// so that artists can see their edits without restarting (see Map::set_surface).
+pub struct TextureWatcher {
+	// block uid and one of its texture files.
+	watchers: Vec<(u8, FileWatcher)>,
+	last_poll: Option<Instant>,
+}
+
+impl TextureWatcher {
+	// Stat-ing all textures every frame would be wasteful.
+	const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+	/// Watch the textures in texture_dir (usually Config::texture_dir).
+	pub fn new(texture_dir: &Path) -> Self {
+		let mut watchers = Vec::new();
+		for def in ED_PALETTE.iter().skip(1) {
+			let base = texture_dir.join(def.srf);
+			for ext in &["hm.png", "dm.png"] {
+				watchers.push((def.uid, FileWatcher::new(&base.with_extension(ext))));
+			}
+		}
+		Self {
+			watchers,
+			last_poll: None,
+		}
+	}
+
+	/// Uids of the blocks whose textures changed since the last call,
+	/// polled at most every POLL_INTERVAL.
+	pub fn changed(&mut self) -> Vec<u8> {
+		match self.last_poll {
+			Some(t) if t.elapsed() < Self::POLL_INTERVAL => return Vec::new(),
+			_ => self.last_poll = Some(Instant::now()),
+		}
+		let mut uids = Vec::new();
+		for (uid, w) in &mut self.watchers {
+			if w.changed() && !uids.contains(uid) {
+				uids.push(*uid);
+			}
+		}
+		uids
+	}
+}
+
 fn zero_vec<T: Default>(len: usize) -> Vec<T> {
 	let mut s = Vec::with_capacity(len);
 	for _ in 0..len {
@@ -217,3 +333,57 @@ fn zero_vec<T: Default>(len: usize) -> Vec<T> {
 fn test_load_palette() {
 	load_palette(&PathBuf::from("assets/textures")).expect("loading palette");
 }
+
+#[test]
+fn test_texture_watcher() {
+	use std::fs;
+	use std::time::SystemTime;
+
+	// per process, so that concurrent test runs do not share files.
+	let dir =
+		std::env::temp_dir().join(format!("flux_test_texture_watcher_{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	let seed: Vec<u8> = ED_PALETTE
+		.iter()
+		.filter(|def| def.srf == "seed")
+		.map(|def| def.uid)
+		.collect();
+	// write a file with a given mtime (seconds apart), so that changes are seen
+	// even if the file system's timestamps are coarse.
+	let write = |file: &str, secs: u64| {
+		let p = dir.join(file);
+		fs::write(&p, b"png").unwrap();
+		let t = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+		fs::File::options()
+			.write(true)
+			.open(&p)
+			.unwrap()
+			.set_modified(t)
+			.unwrap();
+	};
+	write("seed.dm.png", 1000);
+
+	let mut w = TextureWatcher::new(&dir);
+	assert!(w.changed().is_empty());
+	std::thread::sleep(TextureWatcher::POLL_INTERVAL);
+	write("seed.dm.png", 2000);
+	write("seed.hm.png", 2000);
+	assert_eq!(w.changed(), seed);
+	std::thread::sleep(TextureWatcher::POLL_INTERVAL);
+	assert!(w.changed().is_empty());
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_block_types() {
+	// block uids index into the palette: they must be unique and in range.
+	let mut seen = FnvHashSet::default();
+	for def in &ED_PALETTE {
+		assert!((def.uid as usize) < ED_PALETTE.len());
+		assert!(seen.insert(def.uid), "duplicate uid {}", def.uid);
+	}
+	let types = block_types();
+	assert_eq!(types[29], Spikes);
+	assert!(types[29].is_solid() && types[29].is_deadly());
+	assert!(!types[30].is_solid() && types[30].is_deadly());
+}
@@ -0,0 +1,280 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+
+/// Sub-pixel resolution of the hamster's position and speed:
+/// one pixel is SUBPIX fixed-point units.
+pub const SUBPIX: i32 = 256;
+
+/// Convert pixels (per tick, per tick squared, ...) to fixed-point sub-pixel units.
+/// Rounds to the nearest unit, which is deterministic for given input,
+/// so movement stays reproducible for input recording and replays.
+pub fn subpix(pixels: f64) -> i32 {
+	(pixels * SUBPIX as f64).round() as i32
+}
+
+/// Tuning parameters for the hamster's movement.
+/// Speeds are in pixels per tick, accelerations in pixels per tick per tick.
+/// Fractional values are allowed, the hamster moves with sub-pixel precision (see SUBPIX).
+/// Ticks are those of DEFAULT_TICK_RATE, see at_tick_rate for other rates.
+///
+/// Loaded from physics_file(), and optionally overridden per level (see PhysicsOverride).
+/// Fields missing from the file get their built-in default value.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Physics {
+	/// Maximum horizontal speed.
+	pub walk_pix_per_tick: f64,
+	/// Maximum horizontal speed while holding the run button.
+	pub run_pix_per_tick: f64,
+	/// Horizontal accelleration.
+	pub walk_accel: f64,
+	/// Horizontal accelleration on ice.
+	pub ice_accel: f64,
+	/// Speed when the arrow keys are released,
+	/// until aligned with walk_align.
+	pub coast_pix_per_tick: f64,
+	/// Coast until aligned with this number of pixels.
+	/// Makes aiming for an empty space between blocks easier
+	/// and avoids stopping nearly entirely over an edge.
+	pub walk_align: i32,
+
+	/// Maximum duration of a jump while holding the jump key.
+	pub jump_max_ticks: i32,
+	/// Minimum duration of a jump, even when immediately releasing the jump key.
+	pub jump_min_ticks: i32,
+	/// Maximum vertical speed, both up and down.
+	pub jump_pix_per_tick: f64,
+	/// Upward speed while jumping.
+	pub jump_v_init: f64,
+	/// Gravity.
+	pub jump_g: f64,
+	/// Duration of the upward launch by a bouncy block, regardless of the jump key.
+	pub bounce_ticks: i32,
+
+	/// Ignore the arrow keys this long after a wall jump.
+	pub wall_kick_ticks: i32,
+	/// Downward speed while sliding against a wall.
+	pub wall_slide_pix_per_tick: f64,
+	/// Speed on a ladder.
+	pub climb_pix_per_tick: f64,
+}
+
+impl Physics {
+	/// The default physics profile.
+	pub fn new() -> Self {
+		Self {
+			walk_pix_per_tick: 6.0,
+			run_pix_per_tick: 9.0,
+			walk_accel: 1.0,
+			ice_accel: 0.25,
+			coast_pix_per_tick: 2.0,
+			walk_align: (GRID as i32) / 2,
+			jump_max_ticks: 20, // should be ~3 blocks in ~500 ms
+			jump_min_ticks: 7,  // should be ~1 block
+			jump_pix_per_tick: 9.0,
+			jump_v_init: 9.0,
+			jump_g: 2.0,
+			bounce_ticks: 30, // should be ~4.5 blocks
+			wall_kick_ticks: 8,
+			wall_slide_pix_per_tick: 2.0,
+			climb_pix_per_tick: 3.0,
+		}
+	}
+
+	/// The profile for a game running at tick_rate ticks per second:
+	/// speeds, accelerations and durations are scaled so that the hamster
+	/// moves the same in real time as at DEFAULT_TICK_RATE.
+	pub fn at_tick_rate(&self, tick_rate: u32) -> Self {
+		// duration of a tick, relative to a default tick.
+		let r = DEFAULT_TICK_RATE as f64 / tick_rate as f64;
+		let speed = |v: f64| v * r;
+		let accel = |a: f64| a * r * r;
+		let ticks = |t: i32| (t as f64 / r).round() as i32;
+		Self {
+			walk_pix_per_tick: speed(self.walk_pix_per_tick),
+			run_pix_per_tick: speed(self.run_pix_per_tick),
+			walk_accel: accel(self.walk_accel),
+			ice_accel: accel(self.ice_accel),
+			coast_pix_per_tick: speed(self.coast_pix_per_tick),
+			walk_align: self.walk_align,
+			jump_max_ticks: ticks(self.jump_max_ticks),
+			jump_min_ticks: ticks(self.jump_min_ticks),
+			jump_pix_per_tick: speed(self.jump_pix_per_tick),
+			jump_v_init: speed(self.jump_v_init),
+			jump_g: accel(self.jump_g),
+			bounce_ticks: ticks(self.bounce_ticks),
+			wall_kick_ticks: ticks(self.wall_kick_ticks),
+			wall_slide_pix_per_tick: speed(self.wall_slide_pix_per_tick),
+			climb_pix_per_tick: speed(self.climb_pix_per_tick),
+		}
+	}
+
+	/// Load from JSON, e.g. "assets/physics.json".
+	pub fn load(p: &Path) -> Result<Self> {
+		check_exists(p)?;
+		let f = File::open(p)?;
+		let b = BufReader::new(f);
+		let physics: Self = serde_json::from_reader(b)?;
+		physics.validate()?;
+		Ok(physics)
+	}
+
+	/// Check for values that would make the hamster unplayable (or crash the game),
+	/// like a zero or negative speed or gravity.
+	pub fn validate(&self) -> Result<()> {
+		let mut problems = Vec::new();
+		let positive = [
+			("walk_pix_per_tick", self.walk_pix_per_tick),
+			("run_pix_per_tick", self.run_pix_per_tick),
+			("walk_accel", self.walk_accel),
+			("ice_accel", self.ice_accel),
+			("coast_pix_per_tick", self.coast_pix_per_tick),
+			("walk_align", self.walk_align as f64),
+			("jump_max_ticks", self.jump_max_ticks as f64),
+			("jump_pix_per_tick", self.jump_pix_per_tick),
+			("jump_v_init", self.jump_v_init),
+			("jump_g", self.jump_g),
+			("wall_slide_pix_per_tick", self.wall_slide_pix_per_tick),
+			("climb_pix_per_tick", self.climb_pix_per_tick),
+		];
+		for (name, v) in &positive {
+			if v.is_nan() || *v <= 0.0 {
+				problems.push(format!("{} must be positive, got {}", name, v));
+			}
+		}
+		let non_negative = [
+			("jump_min_ticks", self.jump_min_ticks),
+			("bounce_ticks", self.bounce_ticks),
+			("wall_kick_ticks", self.wall_kick_ticks),
+		];
+		for (name, v) in &non_negative {
+			if *v < 0 {
+				problems.push(format!("{} must not be negative, got {}", name, v));
+			}
+		}
+		if self.jump_min_ticks > self.jump_max_ticks {
+			problems.push(format!(
+				"jump_min_ticks ({}) exceeds jump_max_ticks ({})",
+				self.jump_min_ticks, self.jump_max_ticks
+			));
+		}
+		match problems.len() {
+			0 => Ok(()),
+			_ => GenError::new(format!("invalid physics: {}", problems.join(", "))),
+		}
+	}
+
+	/// Load from physics_file(), falling back to the built-in defaults
+	/// (with a warning) if the file is missing or broken.
+	pub fn load_default() -> Self {
+		match Self::load(&physics_file()) {
+			Ok(p) => p,
+			Err(e) => {
+				eprintln!(
+					"loading {}: {}, using default physics",
+					physics_file().to_string_lossy(),
+					e
+				);
+				Self::new()
+			}
+		}
+	}
+}
+
+impl Default for Physics {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Level-specific changes to the physics profile (see LevelData),
+/// e.g. a low-gravity level only sets jump_g.
+/// Holds any subset of the fields of Physics, as JSON, so that new Physics fields
+/// can be overridden without changes here.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PhysicsOverride(serde_json::Map<String, serde_json::Value>);
+
+impl PhysicsOverride {
+	/// The profile with the fields set in this override replaced.
+	pub fn apply(&self, profile: &Physics) -> Result<Physics> {
+		let mut fields = match serde_json::to_value(profile)? {
+			serde_json::Value::Object(fields) => fields,
+			_ => unreachable!("Physics serializes to an object"),
+		};
+		for (name, v) in &self.0 {
+			if !fields.contains_key(name) {
+				return GenError::new(format!("invalid physics: unknown field {}", name));
+			}
+			fields.insert(name.clone(), v.clone());
+		}
+		let p: Physics = serde_json::from_value(serde_json::Value::Object(fields))?;
+		p.validate()?;
+		Ok(p)
+	}
+}
+
+/// The physics profile used by levels that do not override it.
+pub fn physics_file() -> PathBuf {
+	PathBuf::from("assets/physics.json")
+}
+
+#[test]
+fn test_subpix() {
+	assert_eq!(subpix(1.0), SUBPIX);
+	assert_eq!(subpix(-2.5), -5 * SUBPIX / 2);
+	assert_eq!(subpix(1.0 / 512.0), 1); // rounds half away from zero
+}
+
+#[test]
+fn test_load_physics() {
+	// the shipped profile should be the defaults,
+	// so that tweaking starts from the tuned values.
+	assert_eq!(Physics::load(&physics_file()).unwrap(), Physics::new());
+}
+
+#[test]
+fn test_physics_tick_rate() {
+	let p = Physics::new();
+	assert_eq!(p.at_tick_rate(DEFAULT_TICK_RATE), p);
+	let fast = p.at_tick_rate(2 * DEFAULT_TICK_RATE);
+	assert_eq!(fast.walk_pix_per_tick, p.walk_pix_per_tick / 2.0);
+	assert_eq!(fast.jump_g, p.jump_g / 4.0);
+	assert_eq!(fast.jump_max_ticks, p.jump_max_ticks * 2);
+	assert_eq!(fast.walk_align, p.walk_align);
+	assert!(p.at_tick_rate(MIN_TICK_RATE).validate().is_ok());
+}
+
+#[test]
+fn test_partial_physics() {
+	let p: Physics = serde_json::from_str(r#"{"jump_g": 1.5}"#).unwrap();
+	assert_eq!(p.jump_g, 1.5);
+	assert_eq!(p.walk_pix_per_tick, Physics::new().walk_pix_per_tick);
+}
+
+#[test]
+fn test_physics_override() {
+	let mut profile = Physics::new();
+	profile.walk_pix_per_tick = 5.0;
+	let o: PhysicsOverride = serde_json::from_str(r#"{"jump_g": 1.5}"#).unwrap();
+	let p = o.apply(&profile).unwrap();
+	assert_eq!(p.jump_g, 1.5);
+	assert_eq!(p.walk_pix_per_tick, 5.0); // from the profile, not the built-in defaults
+	assert_eq!(serde_json::to_string(&o).unwrap(), r#"{"jump_g":1.5}"#);
+
+	let bad: PhysicsOverride = serde_json::from_str(r#"{"jump_g": -1, "walk_align": 0}"#).unwrap();
+	let err = bad.apply(&profile).unwrap_err().to_string();
+	assert!(
+		err.contains("jump_g") && err.contains("walk_align"),
+		"{}",
+		err
+	);
+	assert!(Physics::new().validate().is_ok());
+
+	let typo: PhysicsOverride = serde_json::from_str(r#"{"jump_gg": 1.5}"#).unwrap();
+	assert!(typo.apply(&profile).is_err());
+	let wrong_type: PhysicsOverride = serde_json::from_str(r#"{"walk_align": 1.5}"#).unwrap();
+	assert!(wrong_type.apply(&profile).is_err());
+}
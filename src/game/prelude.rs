@@ -1,9 +1,18 @@
+pub use super::animation::*;
+pub use super::app::*;
+pub use super::bindings::*;
+pub use super::cli::*;
+pub use super::config::*;
+pub use super::font::*;
+pub use super::frontend::*;
 pub use super::gamestate::*;
 pub use super::hamster::*;
 pub use super::key::*;
 pub use super::leveldata::*;
 pub use super::map::*;
+pub use super::menu::*;
 pub use super::palette::*;
+pub use super::physics::*;
 pub use super::sdl_interface::*;
 pub use super::texture::*;
 pub use super::viewport::*;
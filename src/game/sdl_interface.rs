@@ -1,6 +1,6 @@
-use crate::editor::prelude::*;
 use crate::prelude::*;
 
+use sdl2::controller::GameController;
 use sdl2::event::Event;
 use sdl2::mouse;
 use sdl2::pixels;
@@ -9,44 +9,59 @@ use sdl2::render::Canvas;
 use sdl2::render::TextureCreator;
 use sdl2::video::Window;
 use sdl2::video::WindowContext;
+use sdl2::GameControllerSubsystem;
 use std::collections::HashMap;
 use std::time;
 
 type SDLTexture = sdl2::render::Texture;
 
-pub fn mainloop(game: &mut Editor) -> Result<()> {
+/// Run the game at tick_rate ticks per second, drawing at the display's refresh rate.
+pub fn mainloop(
+	game: &mut dyn App,
+	tick_rate: u32,
+	size: (u32, u32),
+	fullscreen: bool,
+) -> Result<()> {
 	// (0) initialize sdl window
 	let context = sdl2::init()?;
-	let window = context
-		.video()?
-		.window("game", 1920 / 2, 1080 / 2)
-		.resizable()
-		.position_centered()
-		.build()?;
+	let video = context.video()?;
+	let mut window = video.window("game", size.0, size.1);
+	window.resizable().position_centered();
+	if fullscreen {
+		window.fullscreen_desktop();
+	}
+	let window = window.build()?;
 	let canvas = window.into_canvas().accelerated().present_vsync().build()?;
 	let texture_creator = canvas.texture_creator();
 	let mut event_pump = context.event_pump()?;
 
+	// game controllers must be kept open to receive their events.
+	// SDL reports controllers connected at startup as added too.
+	let controller_subsystem = context.game_controller()?;
+	let mut controllers = Vec::new();
+
+	game.set_tick_rate(tick_rate);
+	let mut bindings = Bindings::load_default();
+	for msg in bindings.conflicts() {
+		game.warn(msg);
+	}
+
 	// (1) initialize game logic and display callback (drawback?).
 	let mut disp = SDLDisplay::new(canvas, texture_creator);
 
 	// (2) event + render loop
-	let mut start = time::Instant::now();
+	let mut clock = Timestep::new(tick_rate, MAX_CATCHUP_TICKS);
+	let mut last = time::Instant::now();
 	loop {
-		// Advance time. Tick more than once if frames were dropped due to slow rendering.
-		// TODO: move timekeeping into game (w/ millisecond resolution time)
-		game.tick();
-		{
-			let el = start.elapsed().as_millis();
-			if el > 32 {
-				println!("MISSED FRAME after {}ms, catching up", el);
-				game.tick();
-			}
-			if el > 48 {
-				println!("Degrading from 30 to 20 FPS");
-				game.tick();
-			}
-			start = time::Instant::now();
+		// Advance time by a whole number of ticks, independent of the refresh rate.
+		let now = time::Instant::now();
+		for _ in 0..clock.advance(now - last) {
+			game.tick();
+		}
+		last = now;
+		disp.dropped_ticks = clock.dropped_ticks();
+		if game.quit_requested() {
+			return Ok(());
 		}
 
 		// Event handling
@@ -75,29 +90,61 @@ pub fn mainloop(game: &mut Editor) -> Result<()> {
 				Event::MouseWheel { x, y, .. } => game.mouse_wheel(x, y),
 				Event::KeyDown { keycode, .. } => {
 					if let Some(keycode) = keycode {
-						game.key_down(keymap(keycode));
+						game.key_down(bindings.key(&keycode.name()));
 					}
 				}
 				Event::KeyUp { keycode, .. } => {
 					if let Some(keycode) = keycode {
-						game.key_up(keymap(keycode));
+						game.key_up(bindings.key(&keycode.name()));
 					}
 				}
+				Event::ControllerButtonDown { button, .. } => {
+					game.key_down(bindings.button(&button.string()))
+				}
+				Event::ControllerButtonUp { button, .. } => {
+					game.key_up(bindings.button(&button.string()))
+				}
+				Event::ControllerAxisMotion { axis, value, .. } => {
+					for (k, down) in bindings.axis(&axis.string(), value) {
+						match down {
+							true => game.key_down(k),
+							false => game.key_up(k),
+						}
+					}
+				}
+				Event::ControllerDeviceAdded { which, .. } => {
+					open_controller(&controller_subsystem, which, &mut controllers)
+				}
+				Event::ControllerDeviceRemoved { which, .. } => {
+					// which is the instance id here, not the device index.
+					controllers.retain(|c| c.instance_id() as u32 != which);
+				}
 				_ => (),
 			}
 		}
 
-		game.draw(&mut disp);
+		game.draw(&mut disp, clock.alpha());
 		disp.present();
 	}
 }
 
+/// Maximum number of ticks to catch up after a slow frame.
+/// Beyond that, the game slows down instead.
+const MAX_CATCHUP_TICKS: u32 = 4;
+
 /// Display is an abstraction layer over an SDL Canvas and collection of textures,
 /// So that none of the game logic needs to be concerned with SDL details.
 pub struct SDLDisplay {
 	canvas: Canvas<Window>,
 	texture_creator: TextureCreator<WindowContext>,
 	textures: HashMap<usize, SDLTexture>,
+
+	// frames presented since fps_since, for measuring the frame rate.
+	frames: u32,
+	fps_since: time::Instant,
+	fps: f64,
+	// ticks the main loop dropped so far because drawing could not keep up.
+	dropped_ticks: u64,
 }
 
 impl SDLDisplay {
@@ -107,6 +154,10 @@ impl SDLDisplay {
 			texture_creator,
 			canvas,
 			textures: HashMap::new(),
+			frames: 0,
+			fps_since: time::Instant::now(),
+			fps: 0.0,
+			dropped_ticks: 0,
 		}
 	}
 
@@ -116,7 +167,37 @@ impl SDLDisplay {
 	}
 
 	pub fn present(&mut self) {
-		self.canvas.present()
+		self.canvas.present();
+
+		self.frames += 1;
+		let elapsed = self.fps_since.elapsed().as_secs_f64();
+		if elapsed >= 1.0 {
+			self.fps = self.frames as f64 / elapsed;
+			self.frames = 0;
+			self.fps_since = time::Instant::now();
+		}
+	}
+
+	/// Frames presented per second, averaged over the last second.
+	pub fn fps(&self) -> f64 {
+		self.fps
+	}
+
+	/// Game ticks dropped so far because drawing could not keep up (see Timestep).
+	pub fn dropped_ticks(&self) -> u64 {
+		self.dropped_ticks
+	}
+
+	/// Draw text with the top-left corner at pos, magnifying the font scale times.
+	pub fn draw_text(&mut self, font: &Font, text: &str, pos: Pt, scale: i32) {
+		let dim = (GLYPH_W * scale, GLYPH_H * scale);
+		let glyphs = font.layout(text, scale);
+		for &(_, shadow, p) in &glyphs {
+			self.draw_texture(shadow, pos + p + Pt(scale, scale), dim, false);
+		}
+		for &(glyph, _, p) in &glyphs {
+			self.draw_texture(glyph, pos + p, dim, false);
+		}
 	}
 
 	pub fn fill_rect(&mut self, c: BGRA, pos: Pt, (w, h): (i32, i32)) {
@@ -172,31 +253,19 @@ impl SDLDisplay {
 	}
 }
 
-fn keymap(sdl_key: sdl2::keyboard::Keycode) -> Key {
-	use sdl2::keyboard::Keycode;
-	match sdl_key {
-		Keycode::Left => Key::Left,
-		Keycode::S => Key::Left,
-		Keycode::J => Key::Left,
-		Keycode::Right => Key::Right,
-		Keycode::F => Key::Right,
-		Keycode::L => Key::Right,
-		Keycode::Up => Key::Up,
-		Keycode::E => Key::Up,
-		Keycode::I => Key::Up,
-		Keycode::Down => Key::Down,
-		Keycode::D => Key::Down,
-		Keycode::K => Key::Down,
-		Keycode::Space => Key::A,
-		Keycode::LAlt => Key::B,
-		Keycode::RAlt => Key::B,
-		Keycode::Equals => Key::ZoomIn,
-		Keycode::Minus => Key::ZoomOut,
-		Keycode::P => Key::Pause,
-		Keycode::W => Key::Save,
-		Keycode::N => Key::NextMap,
-		Keycode::M => Key::PrevMap,
-		Keycode::R => Key::Restart,
-		_ => Key::None,
+fn open_controller(
+	subsystem: &GameControllerSubsystem,
+	index: u32,
+	controllers: &mut Vec<GameController>,
+) {
+	if !subsystem.is_game_controller(index) {
+		return;
+	}
+	match subsystem.open(index) {
+		Ok(c) => {
+			println!("game controller: {}", c.name());
+			controllers.push(c);
+		}
+		Err(e) => eprintln!("opening game controller {}: {}", index, e),
 	}
 }
@@ -1,9 +1,8 @@
 use crate::prelude::*;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-pub fn texture_dir() -> PathBuf {
-	PathBuf::from("assets/textures")
-}
+/// Directory textures are loaded from, unless overridden with the --textures flag.
+pub const DEFAULT_TEXTURE_DIR: &str = "assets/textures";
 
 static NEXT_UID: AtomicUsize = AtomicUsize::new(1);
 
@@ -21,17 +20,17 @@ impl Texture {
 		}
 	}
 
-	pub fn load(basename: &str) -> Result<Texture> {
-		let mut path = texture_dir().join(basename);
+	pub fn load(dir: &Path, basename: &str) -> Result<Texture> {
+		let mut path = dir.join(basename);
 		if path.extension() == None {
 			path.set_extension("png");
 		}
 		Ok(Texture::new(Image::<BGRA>::load(&path)?))
 	}
 
-	pub fn load_many(basenames: &[&str]) -> Result<Vec<Texture>> {
+	pub fn load_many(dir: &Path, basenames: &[&str]) -> Result<Vec<Texture>> {
 		//let vec = Vec::with_capacity(basenames.len());
-		basenames.iter().map(|x| Self::load(x)).collect()
+		basenames.iter().map(|x| Self::load(dir, x)).collect()
 	}
 
 	pub fn default() -> Self {
@@ -53,6 +52,10 @@ impl Texture {
 		self.img.dimensions()
 	}
 
+	pub fn image(&self) -> &Image<BGRA> {
+		&self.img
+	}
+
 	pub fn raw_bgra(&self) -> Vec<u8> {
 		self.img.raw_bgra()
 	}
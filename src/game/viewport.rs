@@ -27,6 +27,13 @@ impl<'a> Viewport<'a> {
 		self.disp.draw_texture(tex, pos, (w, h), flip)
 	}
 
+	/// Draw text at a position in the world, e.g. a label in the editor.
+	pub fn draw_text(&mut self, font: &Font, text: &str, pos: Pt, scale: i32) {
+		let pos = self.to_screen(pos);
+		self.disp
+			.draw_text(font, text, pos, max(1, scale / self.zoom))
+	}
+
 	pub fn fill_rect(&mut self, c: BGRA, pos: Pt, dim: (i32, i32)) {
 		let pos = self.to_screen(pos);
 		let dim = self.scale_dim(dim);
@@ -0,0 +1,136 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+
+/// Baked tiles packed into a single image,
+/// with an index telling where each TileKey's tile is.
+/// Saved as a PNG plus a JSON index next to it (see index_file).
+pub struct Atlas {
+	image: Image<BGRA>,
+	index: Vec<AtlasEntry>,
+}
+
+/// Where a tile is in the atlas image.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AtlasEntry {
+	pub key: TileKey,
+	pub rect: Rect,
+}
+
+impl Atlas {
+	/// Pack tiles (all of the same size) into a grid that is about as wide as high.
+	/// Duplicate keys are packed only once.
+	pub fn pack(tiles: &[(TileKey, &Image<BGRA>)]) -> Self {
+		let mut unique = Vec::with_capacity(tiles.len());
+		let mut seen = FnvHashSet::default();
+		for &(key, img) in tiles {
+			if seen.insert(key) {
+				unique.push((key, img));
+			}
+		}
+
+		let dim = unique
+			.first()
+			.map(|(_, img)| img.dimensions())
+			.unwrap_or((0, 0));
+		let n = unique.len() as i32;
+		let cols = (n as f64).sqrt().ceil() as i32;
+		let rows = if cols == 0 { 0 } else { (n + cols - 1) / cols };
+
+		let mut image = Image::new((cols * dim.0, rows * dim.1));
+		let mut index = Vec::with_capacity(unique.len());
+		for (i, (key, img)) in unique.into_iter().enumerate() {
+			assert_eq!(img.dimensions(), dim, "Atlas::pack: tiles differ in size");
+			let i = i as i32;
+			let pos = Pt((i % cols) * dim.0, (i / cols) * dim.1);
+			image.paste(pos.as_tuple(), img);
+			index.push(AtlasEntry {
+				key,
+				rect: Rect::new(pos, dim),
+			});
+		}
+		Self { image, index }
+	}
+
+	pub fn image(&self) -> &Image<BGRA> {
+		&self.image
+	}
+
+	pub fn index(&self) -> &[AtlasEntry] {
+		&self.index
+	}
+
+	/// Position and size of a tile in the atlas image, if present.
+	pub fn rect(&self, key: TileKey) -> Option<Rect> {
+		self.index.iter().find(|e| e.key == key).map(|e| e.rect)
+	}
+
+	/// Copy of a tile, if present.
+	pub fn tile(&self, key: TileKey) -> Option<Image<BGRA>> {
+		self.rect(key)
+			.map(|r| self.image.crop(r.min.as_tuple(), r.dimensions()))
+	}
+
+	/// Save the image as PNG, and the index as JSON next to it.
+	pub fn save(&self, png: &Path) -> Result<()> {
+		self.image.save(png)?;
+		let f = File::create(index_file(png))?;
+		let mut b = BufWriter::new(f);
+		serde_json::to_writer(&mut b, &self.index)?;
+		b.flush()?;
+		Ok(())
+	}
+
+	/// Load an atlas saved by save().
+	pub fn load(png: &Path) -> Result<Self> {
+		let image = Image::<BGRA>::load_saved(png)?;
+		let index_file = index_file(png);
+		check_exists(&index_file)?;
+		let f = File::open(index_file)?;
+		let index = serde_json::from_reader(BufReader::new(f))?;
+		Ok(Self { image, index })
+	}
+}
+
+/// The index file belonging to an atlas image: "tiles.png" -> "tiles.atlas.json".
+/// (Not "tiles.json", which could be the level the atlas was baked from).
+pub fn index_file(png: &Path) -> PathBuf {
+	png.with_extension("atlas.json")
+}
+
+#[test]
+fn test_atlas() {
+	// distinct channels, so that swapping red and blue shows.
+	let tile = |c: u8| Image::from_fn((4, 4), |x, y| BGRA(c, 10 * c + x as u8, 100 + y as u8, 255));
+	let (a, b, c) = (tile(1), tile(2), tile(3));
+	let key = |blk: u8| TileKey::with_center(blk);
+	let atlas = Atlas::pack(&[(key(1), &a), (key(2), &b), (key(1), &a), (key(3), &c)]);
+
+	// 3 unique tiles in a 2x2 grid.
+	assert_eq!(atlas.index().len(), 3);
+	assert_eq!(atlas.image().dimensions(), (8, 8));
+	assert_eq!(atlas.rect(key(2)), Some(Rect::new(Pt(4, 0), (4, 4))));
+	assert_eq!(atlas.rect(key(3)), Some(Rect::new(Pt(0, 4), (4, 4))));
+	assert_eq!(atlas.rect(key(4)), None);
+	assert_eq!(atlas.tile(key(3)), Some(c.clone()));
+
+	// per process, so that concurrent test runs do not share files.
+	let dir = std::env::temp_dir().join(format!("flux_test_atlas_{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	let png = dir.join("tiles.png");
+	atlas.save(&png).unwrap();
+	let loaded = Atlas::load(&png).unwrap();
+	assert_eq!(loaded.index(), atlas.index());
+	assert_eq!(loaded.image(), atlas.image());
+	assert_eq!(loaded.tile(key(3)), Some(c));
+	std::fs::remove_dir_all(&dir).unwrap();
+
+	assert_eq!(
+		index_file(Path::new("tiles.png")),
+		PathBuf::from("tiles.atlas.json")
+	);
+
+	let empty = Atlas::pack(&[]);
+	assert_eq!(empty.image().dimensions(), (0, 0));
+}
@@ -32,6 +32,11 @@ impl BGRA {
 		self.3
 	}
 
+	/// Perceived brightness (0-255) of the sRGB color, ignoring alpha.
+	pub fn luminance(&self) -> f32 {
+		0.2126 * (self.r() as f32) + 0.7152 * (self.g() as f32) + 0.0722 * (self.b() as f32)
+	}
+
 	pub fn linear(&self) -> RGBf {
 		RGBf(
 			srgb_to_linear(self.r()),
@@ -40,6 +45,17 @@ impl BGRA {
 		)
 	}
 
+	/// This diffuse color under the given (linear) light, keeping alpha.
+	pub fn lit(&self, light: RGBf) -> BGRA {
+		let c = self.linear();
+		BGRA(
+			linear_to_srgb8(c.b() * light.0),
+			linear_to_srgb8(c.g() * light.1),
+			linear_to_srgb8(c.r() * light.2),
+			self.a(),
+		)
+	}
+
 	pub const WHITE: BGRA = BGRA(255, 255, 255, 255);
 	pub const BLACK: BGRA = BGRA(0, 0, 0, 255);
 }
@@ -0,0 +1,119 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Denoising preset, trading sharpness (and a little speed) for less noise.
+/// Allows for fewer rays (see Lights) at similar visual quality.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Denoise {
+	#[default]
+	Off,
+	/// Small filter, removes most pixel-level noise.
+	Fast,
+	/// Large filter, for very few rays.
+	Smooth,
+}
+
+impl Denoise {
+	/// Filter radius, in pixels.
+	/// This is also the margin of lighting computed around each tile,
+	/// which may be at most Lights::MAX_MARGIN.
+	pub fn radius(self) -> i32 {
+		match self {
+			Denoise::Off => 0,
+			Denoise::Fast => 2,
+			Denoise::Smooth => 5,
+		}
+	}
+}
+
+/// How much surface normals and heights may differ before pixels are no longer averaged.
+const NORMAL_POWER: i32 = 16;
+const HEIGHT_SIGMA: f64 = 0.01;
+
+/// Edge-aware (cross-bilateral) filter of the lighting term,
+/// guided by the surface normal and height so that shading is not blurred across edges.
+///
+/// The inputs have a margin of radius pixels on all sides,
+/// the output is the filtered interior (i.e. 2*radius smaller in each direction).
+pub fn denoise(
+	light: &Image<RGBf>,
+	normal: &Image<Vec3>,
+	height: &Image<f64>,
+	radius: i32,
+) -> Image<RGBf> {
+	let (w, h) = light.dimensions();
+	let r = radius;
+	let sigma2 = 2.0 * sqr(max(r as f64 / 2.0, 0.5));
+
+	Image::from_fn((w - 2 * r, h - 2 * r), |x, y| {
+		let (x, y) = (x + r, y + r);
+		let n0 = normal.at((x, y));
+		let h0 = height.at((x, y));
+
+		let mut sum = RGBf(0.0, 0.0, 0.0);
+		let mut total = 0.0;
+		for dy in -r..=r {
+			for dx in -r..=r {
+				let p = (x + dx, y + dy);
+				let spatial = -((dx * dx + dy * dy) as f64) / sigma2;
+				let dh = (height.at(p) - h0) / HEIGHT_SIGMA;
+				let w = f64::exp(spatial - dh * dh) * re(n0.dot(normal.at(p))).powi(NORMAL_POWER);
+				sum = sum.add(&light.at(p).mul(w as f32));
+				total += w;
+			}
+		}
+		// total > 0: the central pixel always has weight 1.
+		sum.mul((1.0 / total) as f32)
+	})
+}
+
+fn sqr(x: f64) -> f64 {
+	x * x
+}
+
+#[test]
+fn test_denoise() {
+	let up = Vec3(0.0, 0.0, 1.0);
+	let (w, r) = (16, 3);
+	let flat_n = Image::from_fn((w, w), |_, _| up);
+	let flat_h = Image::<f64>::new((w, w));
+
+	// constant light is unchanged.
+	let c = Image::from_fn((w, w), |_, _| RGBf(0.5, 0.5, 0.5));
+	let out = denoise(&c, &flat_n, &flat_h, r);
+	assert_eq!(out.dimensions(), (w - 2 * r, w - 2 * r));
+	assert!(out.pixels().iter().all(|l| (l.0 - 0.5).abs() < 1e-5));
+
+	// noise on a flat surface is reduced.
+	let noisy = Image::from_fn((w, w), |x, y| {
+		let v = if (x + y) % 2 == 0 { 0.2 } else { 0.8 };
+		RGBf(v, v, v)
+	});
+	let out = denoise(&noisy, &flat_n, &flat_h, r);
+	assert!(out.pixels().iter().all(|l| (l.0 - 0.5).abs() < 0.05));
+
+	// but not averaged across a step in height, nor a crease in the normal.
+	let step = |x: i32| x >= w / 2;
+	let light = Image::from_fn((w, w), |x, _| {
+		if step(x) {
+			RGBf(1.0, 1.0, 1.0)
+		} else {
+			RGBf(0.2, 0.2, 0.2)
+		}
+	});
+	let height = Image::from_fn((w, w), |x, _| if step(x) { 0.3 } else { 0.0 });
+	let out = denoise(&light, &flat_n, &height, r);
+	assert!((out.at((w / 2 - r - 1, 0)).0 - 0.2).abs() < 1e-3);
+	assert!((out.at((w / 2 - r, 0)).0 - 1.0).abs() < 1e-3);
+
+	let crease = Image::from_fn((w, w), |x, _| {
+		if step(x) {
+			Vec3(1.0, 0.0, 1.0).normalized()
+		} else {
+			Vec3(-1.0, 0.0, 1.0).normalized()
+		}
+	});
+	let out = denoise(&light, &crease, &flat_h, r);
+	assert!((out.at((w / 2 - r - 1, 0)).0 - 0.2).abs() < 1e-3);
+	assert!((out.at((w / 2 - r, 0)).0 - 1.0).abs() < 1e-3);
+}
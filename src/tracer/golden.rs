@@ -0,0 +1,261 @@
+//! Golden-image regression tests for the ray tracer.
+//!
+//! Each case renders a fixed TileKey with a fixed palette and Lights,
+//! and compares the result against a reference PNG in testdata/golden.
+//! On failure, the rendered image and a difference image are written to testdata/output.
+//!
+//! To bless new references after an intended change in shading, run:
+//!
+//! ```text
+//! BLESS=1 cargo test golden
+//! ```
+use crate::prelude::*;
+use std::env;
+use std::fs;
+
+fn golden_dir() -> PathBuf {
+	PathBuf::from("testdata/golden")
+}
+
+fn output_dir() -> PathBuf {
+	PathBuf::from("testdata/output")
+}
+
+/// Perceptual tolerance: images are compared by luminance after a 3x3 blur,
+/// so that small differences (e.g. in floating point across platforms) are tolerated,
+/// but changes in shading are not.
+/// MAX_DIFF bounds the difference of any pixel, MEAN_DIFF the average (both out of 255).
+const MAX_DIFF: f32 = 16.0;
+const MEAN_DIFF: f32 = 2.0;
+
+// Palette used by all cases, indexed by block id:
+// 0: empty, 1: flat and low, 2: a dome, 3: a tall wall, 4: a small bump (goody).
+fn palette() -> Vec<Surface> {
+	let w = GRID as i32;
+	let c = (w as f64) / 2.0;
+	let dome = move |r: f64, h: f64| {
+		move |x: i32, y: i32| {
+			let d = ((x as f64 - c).powi(2) + (y as f64 - c).powi(2)).sqrt() / r;
+			(h * (1.0 - d * d).max(0.0).sqrt()) as u8
+		}
+	};
+	let surf = |color: BGRA, f: &dyn Fn(i32, i32) -> u8| {
+		Surface::new(
+			Image::from_fn((w, w), f),
+			Image::from_fn((w, w), |_, _| color),
+		)
+	};
+	vec![
+		Surface::new(Image::new((w, w)), Image::new((w, w))),
+		surf(BGRA(160, 160, 160, 255), &|_, _| 20),
+		surf(BGRA(60, 140, 200, 255), &dome(c, 200.0)),
+		surf(BGRA(90, 90, 120, 255), &|_, _| 255),
+		surf(BGRA(30, 200, 240, 255), &dome(c / 3.0, 120.0)),
+	]
+}
+
+fn lights() -> Lights {
+	Lights::new()
+}
+
+fn render_with(key: TileKey, lights: Lights) -> Image<BGRA> {
+	SharedData::new(Arc::new(palette()), lights).render_central_block(key)
+}
+
+/// Render key and compare against testdata/golden/<name>.png, or write it if BLESS is set.
+fn check_golden(name: &str, key: TileKey) {
+	check_golden_with(name, key, lights())
+}
+
+fn check_golden_with(name: &str, key: TileKey, lights: Lights) {
+	let got = render_with(key, lights);
+	let reference = golden_dir().join(name).with_extension("png");
+
+	if env::var_os("BLESS").is_some() {
+		fs::create_dir_all(golden_dir()).unwrap();
+		got.save(&reference).unwrap();
+		println!("blessed {}", reference.to_string_lossy());
+		return;
+	}
+
+	let want = Image::<BGRA>::load_saved(&reference).unwrap_or_else(|e| {
+		panic!(
+			"{}: {} (run with BLESS=1 to create it)",
+			reference.to_string_lossy(),
+			e
+		)
+	});
+	let (max, mean, diff) = compare(&got, &want);
+	if max > MAX_DIFF || mean > MEAN_DIFF {
+		fs::create_dir_all(output_dir()).unwrap();
+		let out = output_dir().join(format!("golden_{}.png", name));
+		let diff_out = output_dir().join(format!("golden_{}.diff.png", name));
+		got.save(&out).unwrap();
+		diff.save(&diff_out).unwrap();
+		panic!(
+			"{}: differs from reference: max {:.1} (tolerance {}), mean {:.2} (tolerance {}), see {} and {}",
+			name,
+			max,
+			MAX_DIFF,
+			mean,
+			MEAN_DIFF,
+			out.to_string_lossy(),
+			diff_out.to_string_lossy()
+		);
+	}
+}
+
+/// Compare images perceptually.
+/// Returns the maximum and mean blurred luminance difference,
+/// and an image showing the (amplified) differences.
+fn compare(a: &Image<BGRA>, b: &Image<BGRA>) -> (f32, f32, Image<BGRA>) {
+	if a.dimensions() != b.dimensions() {
+		return (255.0, 255.0, Image::new(a.dimensions()));
+	}
+	let (la, lb) = (blur(&luminance(a)), blur(&luminance(b)));
+	let (w, h) = a.dimensions();
+	let d = Image::<f32>::from_fn((w, h), |x, y| (la.at((x, y)) - lb.at((x, y))).abs());
+
+	let max = d.pixels().iter().cloned().fold(0.0, f32::max);
+	let mean = d.pixels().iter().sum::<f32>() / (d.pixels().len() as f32);
+	let diff = Image::from_fn((w, h), |x, y| {
+		let v = (d.at((x, y)) * 8.0).min(255.0) as u8;
+		BGRA(0, 0, v, 255)
+	});
+	(max, mean, diff)
+}
+
+// luminance (0..255, perceptual weights) of sRGB pixels, premultiplied by alpha.
+fn luminance(img: &Image<BGRA>) -> Image<f32> {
+	Image::from_fn(img.dimensions(), |x, y| {
+		let c = img.at((x, y));
+		c.luminance() * (c.a() as f32) / 255.0
+	})
+}
+
+// 3x3 box blur, clamped at the edges.
+fn blur(img: &Image<f32>) -> Image<f32> {
+	let (w, h) = img.dimensions();
+	Image::from_fn((w, h), |x, y| {
+		let mut sum = 0.0;
+		let mut n = 0.0;
+		for dy in -1..=1 {
+			for dx in -1..=1 {
+				let (x, y) = (x + dx, y + dy);
+				if x >= 0 && y >= 0 && x < w && y < h {
+					sum += img.at((x, y));
+					n += 1.0;
+				}
+			}
+		}
+		sum / n
+	})
+}
+
+// TileKey with the given 3x3 blocks around the center.
+fn key(blocks: [[u8; 3]; 3], goody: u8) -> TileKey {
+	let mut k = TileKey::new();
+	for dy in -1..=1 {
+		for dx in -1..=1 {
+			k.set(dx, dy, blocks[(dy + 1) as usize][(dx + 1) as usize]);
+		}
+	}
+	k.goody = goody;
+	k
+}
+
+#[test]
+fn golden_dome() {
+	check_golden("dome", key([[0, 0, 0], [0, 2, 0], [0, 0, 0]], 0));
+}
+
+#[test]
+fn golden_wall_shadow() {
+	// low floor, shadowed by walls on the left and top.
+	check_golden("wall_shadow", key([[3, 3, 3], [3, 1, 0], [1, 1, 1]], 0));
+}
+
+#[test]
+fn golden_goody() {
+	check_golden("goody", key([[0, 0, 0], [1, 1, 1], [1, 1, 1]], 4));
+}
+
+#[test]
+fn golden_denoised() {
+	// few rays, denoised.
+	let mut l = lights();
+	l.sun_rays = 2;
+	l.ambient_rays = 5;
+	l.denoise = Denoise::Smooth;
+	check_golden_with("denoised", key([[3, 3, 3], [3, 2, 0], [1, 1, 1]], 0), l);
+}
+
+#[test]
+fn test_deterministic() {
+	// same inputs, same output, regardless of thread count:
+	// whole tiles spread over the workers, or a single tile split between them.
+	// (keys must be canonical: Renderer drops neighbors that cannot cast shadows,
+	// walls (3) are never dropped). Few rays, to keep the test fast.
+	let mut l = lights();
+	l.sun_rays = 2;
+	l.ambient_rays = 3;
+	let walls = [
+		[[3, 3, 3], [3, 0, 0], [0, 0, 0]],
+		[[0, 3, 3], [0, 0, 3], [0, 0, 3]],
+		[[0, 0, 0], [0, 0, 3], [3, 3, 3]],
+		[[3, 0, 0], [3, 0, 0], [3, 3, 0]],
+	];
+	let mut keys = Vec::new();
+	for &center in &[1, 4] {
+		for (i, &w) in walls.iter().enumerate() {
+			let mut blocks = w;
+			blocks[1][1] = center;
+			keys.push(key(blocks, if i % 2 == 0 { 4 } else { 0 }));
+		}
+	}
+	let want: Vec<_> = keys.iter().map(|&k| render_with(k, l.clone())).collect();
+	assert_eq!(want[0], render_with(keys[0], l.clone()));
+
+	for &threads in &[1, 3] {
+		let opts = RenderOptions {
+			threads,
+			..Default::default()
+		};
+		let mut renderer = Renderer::with_options(palette(), l.clone(), opts);
+		let tex = renderer.render_tiles_blocking(&keys);
+		for (t, a) in tex.iter().zip(&want) {
+			assert_eq!(t.image(), a);
+		}
+
+		let mut renderer = Renderer::with_options(palette(), l.clone(), opts);
+		let tex = renderer.render_tiles_blocking(&keys[..1]);
+		assert_eq!(tex[0].image(), &want[0]);
+	}
+
+	l.seed = 1;
+	assert_ne!(render_with(keys[0], l), want[0]);
+}
+
+#[test]
+fn test_compare() {
+	let a = Image::from_fn((8, 8), |x, _| BGRA(0, 100 + x as u8, 0, 255));
+	let (max, mean, _) = compare(&a, &a);
+	assert_eq!((max, mean), (0.0, 0.0));
+
+	let b = Image::from_fn((8, 8), |x, _| BGRA(0, 200 + x as u8, 0, 255));
+	let (max, mean, diff) = compare(&a, &b);
+	assert!(max > MAX_DIFF && mean > MEAN_DIFF);
+	assert_eq!(diff.dimensions(), (8, 8));
+
+	let (max, _, _) = compare(&a, &Image::new((4, 4)));
+	assert_eq!(max, 255.0);
+
+	let c = Image::from_fn((2, 1), |x, _| BGRA(1, 2, 3 + x as u8, 255));
+	// per process, so that concurrent test runs do not share files.
+	let dir = std::env::temp_dir().join(format!("flux_test_golden_{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	let p = dir.join("png.png");
+	c.save(&p).unwrap();
+	assert_eq!(Image::<BGRA>::load_saved(&p).unwrap(), c);
+	fs::remove_dir_all(&dir).unwrap();
+}
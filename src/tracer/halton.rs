@@ -25,6 +25,45 @@ pub fn halton23_scrambled(i: usize, rand: (f64, f64)) -> (f64, f64) {
 	((u + rand.0) % 1.0, (v + rand.1) % 1.0)
 }
 
+/// Pseudo-random offsets for halton23_scrambled, derived from a hash of the given values
+/// (e.g. seed, tile and pixel coordinates). Unlike a random number generator,
+/// this gives the same result for the same inputs, regardless of thread or rendering order.
+pub fn hash_scramble(values: &[u64]) -> (f64, f64) {
+	let mut h = 0;
+	for &v in values {
+		h = splitmix64(h ^ v);
+	}
+	let h2 = splitmix64(h);
+	(unit_f64(h), unit_f64(h2))
+}
+
+// https://xorshift.di.unimi.it/splitmix64.c
+fn splitmix64(x: u64) -> u64 {
+	let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+	z ^ (z >> 31)
+}
+
+// uniform in [0, 1), from the 53 most significant bits.
+fn unit_f64(x: u64) -> f64 {
+	(x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[test]
+fn test_hash_scramble() {
+	let a = hash_scramble(&[1, 2, 3]);
+	assert_eq!(a, hash_scramble(&[1, 2, 3]));
+	assert_ne!(a, hash_scramble(&[1, 2, 4]));
+	assert_ne!(a, hash_scramble(&[2, 2, 3]));
+	assert!(a.0 >= 0.0 && a.0 < 1.0 && a.1 >= 0.0 && a.1 < 1.0);
+
+	// roughly uniform
+	let n = 10000;
+	let mean = (0..n).map(|i| hash_scramble(&[i]).0).sum::<f64>() / n as f64;
+	assert!((mean - 0.5).abs() < 0.01, "{}", mean);
+}
+
 #[test]
 pub fn test_halton() {
 	assert_eq!(halton(2, 0), 0.5);
@@ -37,6 +37,21 @@ where
 		self[p.1 as usize][p.0 as usize]
 	}
 
+	/// Copy of the rectangle with top-left corner pos and size dim.
+	pub fn crop(&self, pos: (i32, i32), dim: (i32, i32)) -> Self {
+		Self::from_fn(dim, |x, y| self.at((pos.0 + x, pos.1 + y)))
+	}
+
+	/// Copy src into this image, with src's top-left corner at pos.
+	pub fn paste(&mut self, pos: (i32, i32), src: &Self) {
+		let (w, h) = src.dimensions();
+		for y in 0..h {
+			for x in 0..w {
+				self[(pos.1 + y) as usize][(pos.0 + x) as usize] = src.at((x, y));
+			}
+		}
+	}
+
 	/// width of the image, in pixels
 	pub fn width(&self) -> usize {
 		self.dim.0
@@ -121,6 +136,16 @@ impl Image<BGRA> {
 		Ok(dst)
 	}
 
+	/// Load an image written by save(), the inverse of save.
+	/// (load keeps the file's RGBA order, as expected by the texture pipeline).
+	pub fn load_saved<P: AsRef<Path>>(p: P) -> Result<Self> {
+		let img = Self::load(p)?;
+		Ok(Image::from_fn(img.dimensions(), |x, y| {
+			let BGRA(r, g, b, a) = img.at((x, y));
+			BGRA(b, g, r, a)
+		}))
+	}
+
 	pub fn raw_bgra(&self) -> Vec<u8> {
 		let (w, h) = self.dimensions();
 		let mut raw = Vec::with_capacity((w * h * 4) as usize);
@@ -1,7 +1,8 @@
 use crate::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Lights {
 	pub sun_dir: Vec3,
 	pub sun_intens: RGBf,
@@ -11,9 +12,28 @@ pub struct Lights {
 	pub ambient_rays: usize,
 	pub fake_ambient: RGBf,
 	pub invert_dm: bool,
+	/// Seeds the scrambling of light samples.
+	/// Baking the same tile with the same Lights gives identical results.
+	#[serde(default)]
+	pub seed: u64,
+	/// Denoise the lighting, e.g. to allow for fewer rays.
+	#[serde(default)]
+	pub denoise: Denoise,
+	/// How many blocks away (1 or 2, i.e. up to TILE_KEY_RADIUS) blocks can cast shadows.
+	/// 2 avoids long shadows being cut off at tile borders, at the cost of more distinct tiles to bake.
+	#[serde(default = "default_neighborhood")]
+	pub neighborhood: usize,
+}
+
+fn default_neighborhood() -> usize {
+	1
 }
 
 impl Lights {
+	/// Lighting is computed up to this many pixels outside of a tile (see Denoise::radius),
+	/// shadow rays starting there must stay inside the tile's TileKey.
+	pub const MAX_MARGIN: i32 = GRID as i32 / 10;
+
 	pub fn new() -> Self {
 		Self {
 			sun_dir: Vec3(1.0, -1.0, 0.7).normalized(),
@@ -24,7 +44,87 @@ impl Lights {
 			ambient_rays: 31,
 			fake_ambient: RGBf(0.5, 0.5, 0.6).mul(0.0),
 			invert_dm: false,
+			seed: 0,
+			denoise: Denoise::Off,
+			neighborhood: default_neighborhood(),
+		}
+	}
+
+	/// Check for settings the renderer cannot handle (e.g. in a hand-edited level).
+	pub fn validate(&self) -> Result<()> {
+		if !(1..=TILE_KEY_RADIUS).contains(&self.neighborhood) {
+			return GenError::new(format!(
+				"neighborhood {} is not between 1 and {}",
+				self.neighborhood, TILE_KEY_RADIUS
+			));
+		}
+		let r = self.denoise.radius();
+		if r > Self::MAX_MARGIN {
+			return GenError::new(format!(
+				"denoise radius {} is more than {} pixels",
+				r,
+				Self::MAX_MARGIN
+			));
+		}
+		Ok(())
+	}
+
+	/// Copy with the number of rays scaled for the given quality (but at least one).
+	pub fn with_quality(&self, q: Quality) -> Self {
+		let scale = |rays: usize| max(1, (rays as f64 * q.ray_factor()) as usize);
+		let mut l = self.clone();
+		l.sun_rays = scale(self.sun_rays);
+		l.ambient_rays = scale(self.ambient_rays);
+		l
+	}
+
+	/// Number of shadow rays traced to bake one tile,
+	/// including the margin needed for denoising.
+	pub fn rays_per_tile(&self) -> usize {
+		let w = GRID + 2 * self.denoise.radius() as usize;
+		w * w * (self.sun_rays + self.ambient_rays)
+	}
+
+	/// Total light reaching a surface point with the given normal,
+	/// shared by baked tiles and lit sprites, which differ only in what occludes a ray.
+	///
+	/// Traces ambient_rays, then sun_rays shadow rays (sampled with the scrambling rnd),
+	/// LANES at a time into fixed-size buffers (this runs for every pixel):
+	/// occluded(dirs, hit) sets hit[i] if the ray in direction dirs[i] is blocked.
+	pub fn light_at(
+		&self,
+		normal: Vec3,
+		rnd: (f64, f64),
+		mut occluded: impl FnMut(&[Vec3], &mut [bool]),
+	) -> RGBf {
+		let (na, ns) = (self.ambient_rays, self.sun_rays);
+		let dir = |i: usize| match i < na {
+			true => cosine_sphere(halton23_scrambled(i, rnd), normal),
+			false => self.sample_sun_dir(halton23_scrambled(i - na, rnd)),
+		};
+		let mut dirs = [Vec3(0.0, 0.0, 0.0); LANES];
+		let mut hit = [false; LANES];
+		// fraction of unoccluded ambient rays, and of sunlight reaching the surface.
+		let (mut ambient, mut sun) = (0.0, 0.0);
+		for i0 in (0..na + ns).step_by(LANES) {
+			let n = min(LANES, na + ns - i0);
+			let (dirs, hit) = (&mut dirs[..n], &mut hit[..n]);
+			for (j, d) in dirs.iter_mut().enumerate() {
+				*d = dir(i0 + j);
+			}
+			occluded(dirs, hit);
+			for (j, (&h, &d)) in hit.iter().zip(dirs.iter()).enumerate() {
+				match (h, i0 + j < na) {
+					(true, _) => (),
+					(false, true) => ambient += 1.0 / na as f64,
+					(false, false) => sun += re(normal.dot(d)) / ns as f64,
+				}
+			}
 		}
+
+		let ambient = self.ambient.mul(ambient as f32);
+		let sunlight = self.sun_intens.mul(sun as f32);
+		ambient.add(&sunlight).add(&self.fake_ambient)
 	}
 
 	pub fn sample_sun_dir(&self, (u, v): (f64, f64)) -> Vector<f64> {
@@ -33,3 +133,77 @@ impl Lights {
 		dir.normalized()
 	}
 }
+
+/// Light quality: trades the number of rays traced per pixel for speed.
+/// Medium uses the rays as stored in the level.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Quality {
+	Low,
+	Medium,
+	High,
+}
+
+impl Quality {
+	fn ray_factor(self) -> f64 {
+		match self {
+			Quality::Low => 0.25,
+			Quality::Medium => 1.0,
+			Quality::High => 2.0,
+		}
+	}
+}
+
+impl FromStr for Quality {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		match s {
+			"low" => Ok(Quality::Low),
+			"medium" => Ok(Quality::Medium),
+			"high" => Ok(Quality::High),
+			_ => GenError::new(format!("unknown quality: {} (want low, medium or high)", s)),
+		}
+	}
+}
+
+impl Default for Quality {
+	fn default() -> Self {
+		Quality::Medium
+	}
+}
+
+#[test]
+fn test_with_quality() {
+	let l = Lights::new();
+	assert_eq!(l.with_quality(Quality::Medium).sun_rays, l.sun_rays);
+	assert_eq!(
+		l.with_quality(Quality::High).ambient_rays,
+		2 * l.ambient_rays
+	);
+	assert_eq!(l.with_quality(Quality::Low).sun_rays, 1);
+	assert_eq!("high".parse::<Quality>().unwrap(), Quality::High);
+	assert!("ultra".parse::<Quality>().is_err());
+}
+
+#[test]
+fn test_rays_per_tile() {
+	let mut l = Lights::new();
+	l.sun_rays = 2;
+	l.ambient_rays = 3;
+	assert_eq!(l.rays_per_tile(), GRID * GRID * 5);
+	l.denoise = Denoise::Fast;
+	assert_eq!(l.rays_per_tile(), (GRID + 4) * (GRID + 4) * 5);
+}
+
+#[test]
+fn test_validate() {
+	let mut l = Lights::new();
+	l.denoise = Denoise::Smooth;
+	assert!(l.validate().is_ok());
+	l.neighborhood = TILE_KEY_RADIUS;
+	assert!(l.validate().is_ok());
+	l.neighborhood = TILE_KEY_RADIUS + 1;
+	assert!(l.validate().is_err());
+	l.neighborhood = 0;
+	assert!(l.validate().is_err());
+}
@@ -1,10 +1,18 @@
+pub mod atlas;
 pub mod color;
+pub mod denoise;
+#[cfg(test)]
+mod golden;
 pub mod halton;
 pub mod image;
 pub mod lights;
 pub mod mappings;
-pub mod mpmc;
+pub mod packet;
+pub mod pool;
 pub mod prelude;
+pub mod procedural;
 pub mod ray;
 pub mod renderer;
+pub mod seam;
+pub mod sprite;
 pub mod surface;
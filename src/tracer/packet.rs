@@ -0,0 +1,157 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// How shadow rays are traced (see SharedData::set_marcher).
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Marcher {
+	/// One ray at a time, in f64, looking up heights in the palette.
+	/// Slow, kept as reference implementation.
+	Scalar,
+	/// All rays of a pixel together, in f32, through a HeightField sampled once per tile.
+	#[default]
+	Packet,
+}
+
+impl FromStr for Marcher {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		match s {
+			"scalar" => Ok(Marcher::Scalar),
+			"packet" => Ok(Marcher::Packet),
+			_ => GenError::new(format!("unknown marcher: {} (want scalar or packet)", s)),
+		}
+	}
+}
+
+/// Number of rays marched in lock-step.
+pub const LANES: usize = 8;
+
+/// Surface heights around a tile, one f32 per pixel,
+/// covering the (2n+1)x(2n+1) blocks of a tile's neighborhood.
+pub struct HeightField {
+	// absolute position (in blocks) of the top-left corner.
+	lo: f32,
+	// width and height, in pixels.
+	size: usize,
+	// highest sample: rays above can no longer hit anything.
+	max: f32,
+	heights: Vec<f32>,
+}
+
+impl HeightField {
+	/// Sample height (a function of absolute position, in blocks)
+	/// at the center of each pixel of the neighborhood of radius n around the central block.
+	pub fn new(n: usize, height: impl Fn(Vec2) -> f64) -> Self {
+		let lo = (TILE_KEY_RADIUS - n) as f64;
+		let size = (2 * n + 1) * GRID;
+		let mut heights = Vec::with_capacity(size * size);
+		for iy in 0..size {
+			for ix in 0..size {
+				let x = lo + (ix as f64 + 0.5) / GRID as f64;
+				let y = lo + (iy as f64 + 0.5) / GRID as f64;
+				heights.push(height(Vec2(x, y)) as f32);
+			}
+		}
+		let max = heights.iter().cloned().fold(0.0, f32::max);
+		Self {
+			lo: lo as f32,
+			size,
+			max,
+			heights,
+		}
+	}
+
+	/// For each ray from start with direction dirs[i]: does it hit the surface?
+	/// The answers are stored in hit[i], which must be as long as dirs.
+	/// Rays leaving the neighborhood sideways are considered unoccluded,
+	/// rays pointing down always hit.
+	pub fn occluded(&self, start: Vec3, dirs: &[Vec3], hit: &mut [bool]) {
+		assert_eq!(dirs.len(), hit.len());
+		hit.fill(false);
+		for (dirs, hit) in dirs.chunks(LANES).zip(hit.chunks_mut(LANES)) {
+			self.occluded_packet(start, dirs, hit);
+		}
+	}
+
+	// March up to LANES rays in lock-step, until all of them hit or escape.
+	// Positions are in pixels (x, y) relative to the top-left corner, and height units (z).
+	fn occluded_packet(&self, start: Vec3, dirs: &[Vec3], hit: &mut [bool]) {
+		let g = GRID as f32;
+		let size = self.size as f32;
+		let (mut x, mut y, mut z) = ([0f32; LANES], [0f32; LANES], [0f32; LANES]);
+		let (mut dx, mut dy, mut dz) = ([0f32; LANES], [0f32; LANES], [0f32; LANES]);
+		let mut active = [false; LANES];
+		let mut num_active = 0;
+
+		for (i, d) in dirs.iter().enumerate() {
+			// a ray pointing down will eventually hit something for sure.
+			if d.z() <= 0.0 {
+				hit[i] = true;
+				continue;
+			}
+			// same stride as the scalar marcher:
+			// about 0.7 pixels per step in the XY plane, starting 2 strides away.
+			let stride = (0.7 / (d.z().cos() * GRID as f64)) as f32;
+			dx[i] = d.x() as f32 * stride * g;
+			dy[i] = d.y() as f32 * stride * g;
+			dz[i] = d.z() as f32 * stride;
+			x[i] = (start.x() as f32 - self.lo) * g + 2.0 * dx[i];
+			y[i] = (start.y() as f32 - self.lo) * g + 2.0 * dy[i];
+			z[i] = start.z() as f32 + 2.0 * dz[i];
+			active[i] = true;
+			num_active += 1;
+		}
+
+		while num_active > 0 {
+			for i in 0..dirs.len() {
+				if !active[i] {
+					continue;
+				}
+				if z[i] > self.max || x[i] < 0.0 || y[i] < 0.0 || x[i] >= size || y[i] >= size {
+					active[i] = false;
+					num_active -= 1;
+					continue;
+				}
+				if self.heights[(y[i] as usize) * self.size + (x[i] as usize)] > z[i] {
+					hit[i] = true;
+					active[i] = false;
+					num_active -= 1;
+					continue;
+				}
+				x[i] += dx[i];
+				y[i] += dy[i];
+				z[i] += dz[i];
+			}
+		}
+	}
+}
+
+#[test]
+fn test_occluded() {
+	// flat floor, with a wall at x >= c+1/2 (absolute position).
+	let c = TILE_KEY_RADIUS as f64;
+	let hf = HeightField::new(1, |p| if p.x() > c + 0.5 { 0.5 } else { 0.0 });
+	assert_eq!(hf.size, 3 * GRID);
+	assert_eq!(hf.max, 0.5);
+
+	let start = Vector(c + 0.25, c + 0.5, 0.01);
+	let dirs = [
+		Vec3(0.0, 0.0, 1.0),                 // up
+		Vec3(1.0, 0.0, 0.01).normalized(),   // into the wall
+		Vec3(-1.0, 0.0, 0.01).normalized(),  // away from the wall
+		Vec3(1.0, 0.0, 2.0).normalized(),    // over the wall
+		Vec3(0.0, 1.0, -0.1).normalized(),   // down
+		Vec3(0.0, 1.0, 0.01).normalized(),   // along the wall
+		Vec3(1.0, 1.0, 0.2).normalized(),    // diagonally into the wall
+		Vec3(-1.0, -1.0, 0.01).normalized(), // diagonally away
+		Vec3(1.0, 0.0, 0.1).normalized(),    // 9th ray: second packet
+	];
+	let mut hit = [false; 9];
+	hf.occluded(start, &dirs, &mut hit);
+	assert_eq!(
+		hit,
+		[false, true, false, false, true, false, true, false, true]
+	);
+}
@@ -0,0 +1,293 @@
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering::SeqCst};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{spawn, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Work-stealing thread pool.
+///
+/// Each worker has its own job queue, so that idle workers do not all contend on one lock.
+/// Jobs are distributed round-robin over the queues,
+/// workers that run out of work steal from the others.
+/// Urgent jobs go to a queue of their own, which every worker checks first.
+///
+/// Dropping the pool stops the workers (after their current job) and joins them.
+/// Jobs that did not start yet are discarded.
+/// A panicking job does not take its worker down (see num_panics).
+pub struct Pool {
+	shared: Arc<Shared>,
+	workers: Vec<JoinHandle<()>>,
+	window: Mutex<Window>,
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Shared {
+	urgent: Mutex<VecDeque<Job>>,
+	// jobs in urgent, so that workers only take its lock when there are any.
+	num_urgent: AtomicUsize,
+	queues: Vec<Mutex<VecDeque<Job>>>,
+	// jobs in the queues, not yet started.
+	queued: AtomicUsize,
+	// round-robin index of the next queue to push to.
+	next: AtomicUsize,
+	// idle workers sleep here.
+	sleep: Mutex<()>,
+	wakeup: Condvar,
+	shutdown: AtomicBool,
+	// per worker: nanoseconds spent running jobs.
+	busy_nanos: Vec<AtomicU64>,
+	panics: AtomicUsize,
+}
+
+/// utilization() is measured over windows of at least this long.
+const UTIL_WINDOW: Duration = Duration::from_secs(1);
+
+// The current utilization window, and the utilization over the previous one (if any).
+struct Window {
+	start: Instant,
+	busy_nanos: Vec<u64>,
+	last: Option<Vec<f64>>,
+}
+
+impl Pool {
+	/// Start a pool with n worker threads (at least one).
+	pub fn new(n: usize) -> Self {
+		let shared = Arc::new(Shared::new(n.max(1)));
+		let workers = (0..shared.queues.len())
+			.map(|i| {
+				let shared = shared.clone();
+				spawn(move || shared.work(i))
+			})
+			.collect();
+		let window = Mutex::new(Window {
+			start: Instant::now(),
+			busy_nanos: vec![0; shared.queues.len()],
+			last: None,
+		});
+		Self {
+			shared,
+			workers,
+			window,
+		}
+	}
+
+	/// Number of worker threads.
+	pub fn num_workers(&self) -> usize {
+		self.shared.queues.len()
+	}
+
+	/// Queue a job, to be run by any worker.
+	pub fn spawn<F: FnOnce() + Send + 'static>(&self, job: F) {
+		self.shared.push(Box::new(job), false)
+	}
+
+	/// Queue a job to be started before all non-urgent jobs (by the next worker that is free),
+	/// and after the urgent jobs queued before it.
+	pub fn spawn_urgent<F: FnOnce() + Send + 'static>(&self, job: F) {
+		self.shared.push(Box::new(job), true)
+	}
+
+	/// Number of jobs waiting to be started.
+	pub fn queue_depth(&self) -> usize {
+		self.shared.queued.load(SeqCst)
+	}
+
+	/// Number of jobs that panicked.
+	pub fn num_panics(&self) -> usize {
+		self.shared.panics.load(SeqCst)
+	}
+
+	/// Per worker: the fraction of time (0..1) spent running jobs
+	/// over the latest UTIL_WINDOW (or so), or since the pool started if that is more recent.
+	/// Jobs count when they are done, so a long job finishing counts in one window
+	/// (capped at 1).
+	pub fn utilization(&self) -> Vec<f64> {
+		let busy: Vec<u64> = self
+			.shared
+			.busy_nanos
+			.iter()
+			.map(|b| b.load(SeqCst))
+			.collect();
+		let mut w = self.window.lock().unwrap();
+		let elapsed = w.start.elapsed();
+		let util: Vec<f64> = busy
+			.iter()
+			.zip(&w.busy_nanos)
+			.map(|(&b, &b0)| f64::min(1.0, (b - b0) as f64 / elapsed.as_nanos() as f64))
+			.collect();
+		if elapsed >= UTIL_WINDOW {
+			*w = Window {
+				start: Instant::now(),
+				busy_nanos: busy,
+				last: Some(util.clone()),
+			};
+		}
+		w.last.clone().unwrap_or(util)
+	}
+}
+
+impl Drop for Pool {
+	fn drop(&mut self) {
+		self.shared.shutdown.store(true, SeqCst);
+		{
+			let _guard = self.shared.sleep.lock().unwrap();
+			self.shared.wakeup.notify_all();
+		}
+		for w in self.workers.drain(..) {
+			w.join().expect("pool worker panicked");
+		}
+	}
+}
+
+impl Shared {
+	fn new(n: usize) -> Self {
+		Self {
+			urgent: Mutex::new(VecDeque::new()),
+			num_urgent: AtomicUsize::new(0),
+			queues: (0..n).map(|_| Mutex::new(VecDeque::new())).collect(),
+			queued: AtomicUsize::new(0),
+			next: AtomicUsize::new(0),
+			sleep: Mutex::new(()),
+			wakeup: Condvar::new(),
+			shutdown: AtomicBool::new(false),
+			busy_nanos: (0..n).map(|_| AtomicU64::new(0)).collect(),
+			panics: AtomicUsize::new(0),
+		}
+	}
+
+	fn push(&self, job: Job, urgent: bool) {
+		self.queued.fetch_add(1, SeqCst);
+		match urgent {
+			true => {
+				let mut urgent = self.urgent.lock().unwrap();
+				urgent.push_back(job);
+				self.num_urgent.fetch_add(1, SeqCst);
+			}
+			false => {
+				let i = self.next.fetch_add(1, SeqCst) % self.queues.len();
+				self.queues[i].lock().unwrap().push_back(job);
+			}
+		}
+		let _guard = self.sleep.lock().unwrap();
+		self.wakeup.notify_one();
+	}
+
+	// Main loop of worker i.
+	fn work(&self, i: usize) {
+		while !self.shutdown.load(SeqCst) {
+			match self.pop(i) {
+				Some(job) => {
+					let start = Instant::now();
+					// the panic hook has already reported it.
+					if catch_unwind(AssertUnwindSafe(job)).is_err() {
+						self.panics.fetch_add(1, SeqCst);
+					}
+					self.busy_nanos[i].fetch_add(start.elapsed().as_nanos() as u64, SeqCst);
+				}
+				None => {
+					let guard = self.sleep.lock().unwrap();
+					if self.queued.load(SeqCst) == 0 && !self.shutdown.load(SeqCst) {
+						// timeout: in case a job got pushed to a queue we just looked at.
+						let _ = self.wakeup.wait_timeout(guard, Duration::from_millis(50));
+					}
+				}
+			}
+		}
+	}
+
+	// Next job for worker i: an urgent one, from its own queue, or stolen from another.
+	fn pop(&self, i: usize) -> Option<Job> {
+		let n = self.queues.len();
+		let job = self
+			.pop_urgent()
+			.or_else(|| (0..n).find_map(|k| self.queues[(i + k) % n].lock().unwrap().pop_front()));
+		if job.is_some() {
+			self.queued.fetch_sub(1, SeqCst);
+		}
+		job
+	}
+
+	fn pop_urgent(&self) -> Option<Job> {
+		if self.num_urgent.load(SeqCst) == 0 {
+			return None;
+		}
+		let job = self.urgent.lock().unwrap().pop_front();
+		if job.is_some() {
+			self.num_urgent.fetch_sub(1, SeqCst);
+		}
+		job
+	}
+}
+
+#[test]
+fn test_pool() {
+	use std::sync::mpsc::channel;
+
+	let pool = Pool::new(3);
+	assert_eq!(pool.num_workers(), 3);
+	let (send, recv) = channel();
+	for i in 0..100 {
+		let send = send.clone();
+		pool.spawn(move || send.send(i).unwrap());
+	}
+	let mut got: Vec<i32> = recv.iter().take(100).collect();
+	got.sort();
+	assert_eq!(got, (0..100).collect::<Vec<_>>());
+	assert_eq!(pool.queue_depth(), 0);
+	assert_eq!(pool.utilization().len(), 3);
+
+	// urgent jobs overtake queued ones.
+	let pool = Pool::new(1);
+	let (send, recv) = channel();
+	let (block, unblock) = channel::<()>();
+	pool.spawn(move || unblock.recv().unwrap());
+	for &(v, urgent) in &[(1, false), (2, false), (3, true)] {
+		let send = send.clone();
+		match urgent {
+			true => pool.spawn_urgent(move || send.send(v).unwrap()),
+			false => pool.spawn(move || send.send(v).unwrap()),
+		}
+	}
+	block.send(()).unwrap();
+	assert_eq!(recv.iter().take(3).collect::<Vec<_>>(), vec![3, 1, 2]);
+
+	// also with several workers: whichever worker is free next takes the urgent jobs first,
+	// even if its own queue is not empty (queues without workers, to control who pops).
+	let s = Shared::new(3);
+	let (send, recv) = channel();
+	for &(v, urgent) in &[(1, false), (2, false), (3, false), (4, true), (5, true)] {
+		let send = send.clone();
+		s.push(Box::new(move || send.send(v).unwrap()), urgent);
+	}
+	for &i in &[1, 1, 2, 2, 0] {
+		s.pop(i).unwrap()();
+	}
+	assert!(s.pop(0).is_none());
+	assert_eq!(s.queued.load(SeqCst), 0);
+	assert_eq!(s.num_urgent.load(SeqCst), 0);
+	assert_eq!(recv.try_iter().collect::<Vec<_>>(), vec![4, 5, 3, 1, 2]);
+
+	// a panic does not stop the worker.
+	let pool = Pool::new(1);
+	let (send, recv) = channel();
+	pool.spawn(|| panic!("test panic"));
+	pool.spawn(move || send.send(1).unwrap());
+	assert_eq!(recv.recv().unwrap(), 1);
+	assert_eq!(pool.num_panics(), 1);
+
+	// drop joins the workers, even if busy.
+	let pool = Pool::new(2);
+	let done = Arc::new(AtomicBool::new(false));
+	let d = done.clone();
+	let (started, wait_started) = channel();
+	pool.spawn(move || {
+		started.send(()).unwrap();
+		std::thread::sleep(Duration::from_millis(20));
+		d.store(true, SeqCst);
+	});
+	wait_started.recv().unwrap();
+	drop(pool);
+	assert!(done.load(SeqCst));
+}
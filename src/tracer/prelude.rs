@@ -1,9 +1,15 @@
+pub use crate::tracer::atlas::*;
 pub use crate::tracer::color::*;
+pub use crate::tracer::denoise::*;
 pub use crate::tracer::halton::*;
 pub use crate::tracer::image::*;
 pub use crate::tracer::lights::*;
 pub use crate::tracer::mappings::*;
-pub use crate::tracer::mpmc::*;
+pub use crate::tracer::packet::*;
+pub use crate::tracer::pool::*;
+pub use crate::tracer::procedural::*;
 pub use crate::tracer::ray::*;
 pub use crate::tracer::renderer::*;
+pub use crate::tracer::seam::*;
+pub use crate::tracer::sprite::*;
 pub use crate::tracer::surface::*;
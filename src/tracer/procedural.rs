@@ -0,0 +1,436 @@
+//! Procedural textures, built by hmpaint from recipes like:
+//!
+//! ```text
+//! {
+//!     "steel-brick": {
+//!         "height": {"Max": [
+//!             {"Bricks": {"rows": 4, "cols": 2, "mortar": 0.03, "bevel": 0.05}},
+//!             {"Rivets": {"n": 4, "radius": 0.04}}
+//!         ]},
+//!         "diffuse": {"low": [40, 40, 50], "high": [150, 160, 170]}
+//!     }
+//! }
+//! ```
+//!
+//! Heights are functions of texture coordinates (u, v) in [0, 1), with values 0 (low) to 1 (high).
+//! All patterns tile seamlessly, so that neighboring blocks join up.
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+
+/// A heightmap, as a tree of patterns and operators.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+	Const(f64),
+	/// Smooth noise, with cells x cells lattice points in the tile,
+	/// each octave adding detail at twice the resolution and half the amplitude.
+	Perlin {
+		cells: u32,
+		octaves: u32,
+		seed: u64,
+	},
+	/// Cellular noise: distance to the nearest of cells x cells random points,
+	/// 0 at the points, about 1 between them.
+	Worley {
+		cells: u32,
+		seed: u64,
+	},
+	/// Running bond: 1 on bricks, 0 in the mortar (width in tile units),
+	/// rising over a bevel of the given width at the edges.
+	/// Rows should be even, every other row is shifted by half a brick.
+	Bricks {
+		rows: u32,
+		cols: u32,
+		mortar: f64,
+		bevel: f64,
+	},
+	/// Plate covering the whole tile, with bevelled edges of the given width.
+	Bevel(f64),
+	/// Hemispheres of the given radius, centered in an n x n grid.
+	Rivets {
+		n: u32,
+		radius: f64,
+	},
+	/// Half-cylinders running vertically, n per tile.
+	Ridges {
+		n: u32,
+		radius: f64,
+	},
+	/// a * mul + add.
+	Scale {
+		a: Box<Pattern>,
+		mul: f64,
+		add: f64,
+	},
+	/// Linear interpolation, from a (t = 0) to b (t = 1).
+	Blend {
+		a: Box<Pattern>,
+		b: Box<Pattern>,
+		t: f64,
+	},
+	Min(Vec<Pattern>),
+	Max(Vec<Pattern>),
+}
+
+/// Diffuse color by height (or by another pattern),
+/// from low (at 0) to high (at 1).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Gradient {
+	pub low: [u8; 3],
+	pub high: [u8; 3],
+	/// Pattern selecting the color, instead of the height.
+	#[serde(default)]
+	pub by: Option<Pattern>,
+}
+
+/// How to build a surface.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recipe {
+	pub height: Pattern,
+	/// Procedural diffuse map, if any.
+	#[serde(default)]
+	pub diffuse: Option<Gradient>,
+}
+
+/// Load named recipes from a JSON file.
+pub fn load_recipes(p: &Path) -> Result<BTreeMap<String, Recipe>> {
+	check_exists(p)?;
+	let f = File::open(p)?;
+	let recipes: BTreeMap<String, Recipe> = serde_json::from_reader(BufReader::new(f))?;
+	for (name, recipe) in &recipes {
+		if let Err(e) = recipe.check() {
+			return GenError::new(format!("recipe {}: {}", name, e));
+		}
+	}
+	Ok(recipes)
+}
+
+/// Finest Perlin lattice (cells << (octaves - 1)), far beyond any texture's pixels.
+/// Also keeps the lattice coordinates exact in f64.
+pub const MAX_PERLIN_PERIOD: u64 = 1 << 24;
+
+impl Pattern {
+	/// Reject parameters the pattern can't be evaluated with.
+	pub fn check(&self) -> Result<()> {
+		use Pattern::*;
+		match self {
+			Perlin { cells, octaves, .. } => {
+				let octaves = max(1, *octaves);
+				let period = match octaves <= 32 {
+					true => (max(1, *cells) as u64) << (octaves - 1),
+					false => u64::MAX,
+				};
+				if period > MAX_PERLIN_PERIOD {
+					return GenError::new(format!(
+						"Perlin: {} cells with {} octaves is finer than {} cells",
+						cells, octaves, MAX_PERLIN_PERIOD
+					));
+				}
+				Ok(())
+			}
+			Scale { a, .. } => a.check(),
+			Blend { a, b, .. } => {
+				a.check()?;
+				b.check()
+			}
+			Min(hs) | Max(hs) => hs.iter().try_for_each(Pattern::check),
+			_ => Ok(()),
+		}
+	}
+
+	/// Height at texture coordinates (u, v).
+	pub fn at(&self, u: f64, v: f64) -> f64 {
+		use Pattern::*;
+		match self {
+			Const(h) => *h,
+			Perlin {
+				cells,
+				octaves,
+				seed,
+			} => perlin(u, v, *cells, *octaves, *seed),
+			Worley { cells, seed } => worley(u, v, *cells, *seed),
+			Bricks {
+				rows,
+				cols,
+				mortar,
+				bevel,
+			} => bricks(u, v, *rows, *cols, *mortar, *bevel),
+			Bevel(width) => ramp(min(min(u, 1.0 - u), min(v, 1.0 - v)), *width),
+			Rivets { n, radius } => {
+				let n = *n as f64;
+				let x = (fract(u * n) - 0.5) / n;
+				let y = (fract(v * n) - 0.5) / n;
+				dome((x * x + y * y).sqrt(), *radius)
+			}
+			Ridges { n, radius } => {
+				let n = *n as f64;
+				dome(((fract(u * n) - 0.5) / n).abs(), *radius)
+			}
+			Scale { a, mul, add } => a.at(u, v) * mul + add,
+			Blend { a, b, t } => a.at(u, v) * (1.0 - t) + b.at(u, v) * t,
+			Min(hs) => hs.iter().map(|h| h.at(u, v)).fold(INF, f64::min),
+			Max(hs) => hs.iter().map(|h| h.at(u, v)).fold(-INF, f64::max),
+		}
+	}
+}
+
+impl Recipe {
+	/// Reject patterns that can't be evaluated (see Pattern::check).
+	pub fn check(&self) -> Result<()> {
+		self.height.check()?;
+		match self.diffuse.as_ref().and_then(|g| g.by.as_ref()) {
+			Some(by) => by.check(),
+			None => Ok(()),
+		}
+	}
+
+	/// Heightmap of size x size pixels, sampled at the pixel centers.
+	pub fn height_map(&self, size: i32) -> Image<u8> {
+		sample(size, |u, v| to_u8(self.height.at(u, v)))
+	}
+
+	/// Diffuse map of size x size pixels, if the recipe has one.
+	pub fn diffuse_map(&self, size: i32) -> Option<Image<BGRA>> {
+		let g = self.diffuse.as_ref()?;
+		let by = g.by.as_ref().unwrap_or(&self.height);
+		Some(sample(size, |u, v| {
+			let t = clip01(by.at(u, v));
+			let c = |i: usize| to_u8((g.low[i] as f64 * (1.0 - t) + g.high[i] as f64 * t) / 255.0);
+			BGRA(c(2), c(1), c(0), 255)
+		}))
+	}
+}
+
+const INF: f64 = f64::INFINITY;
+
+fn sample<T: Copy + Default>(size: i32, f: impl Fn(f64, f64) -> T) -> Image<T> {
+	let s = size as f64;
+	Image::from_fn((size, size), |x, y| {
+		f((x as f64 + 0.5) / s, (y as f64 + 0.5) / s)
+	})
+}
+
+fn fract(x: f64) -> f64 {
+	x - x.floor()
+}
+
+fn clip01(x: f64) -> f64 {
+	if x > 1.0 {
+		1.0
+	} else if x > 0.0 {
+		x
+	} else {
+		0.0 // also NaN
+	}
+}
+
+fn to_u8(x: f64) -> u8 {
+	(clip01(x) * 255.0).round() as u8
+}
+
+// 0 at distance 0, rising linearly to 1 at distance width.
+fn ramp(d: f64, width: f64) -> f64 {
+	if width <= 0.0 {
+		return if d > 0.0 { 1.0 } else { 0.0 };
+	}
+	clip01(d / width)
+}
+
+// Hemisphere profile at distance r from the center, scaled to height 1.
+fn dome(r: f64, radius: f64) -> f64 {
+	let x = r / radius;
+	if x >= 1.0 {
+		0.0
+	} else {
+		(1.0 - x * x).sqrt()
+	}
+}
+
+// Pseudo-random point in [0, 1)², for lattice point (ix, iy) wrapped to the period.
+fn lattice(ix: i64, iy: i64, period: i64, seed: u64) -> (f64, f64) {
+	let wrap = |i: i64| i.rem_euclid(period) as u64;
+	hash_scramble(&[seed, wrap(ix), wrap(iy)])
+}
+
+fn perlin(u: f64, v: f64, cells: u32, octaves: u32, seed: u64) -> f64 {
+	let mut sum = 0.0;
+	let mut amplitude = 1.0;
+	let mut total = 0.0;
+	for o in 0..max(1, octaves) {
+		let period = (cells.max(1) as i64) << o;
+		sum += amplitude
+			* gradient_noise(
+				u * period as f64,
+				v * period as f64,
+				period,
+				seed + o as u64,
+			);
+		total += amplitude;
+		amplitude /= 2.0;
+	}
+	// gradient noise is within about ±0.7.
+	clip01(0.5 + 0.7 * sum / total)
+}
+
+// Classic 2D Perlin noise, periodic with the given period.
+fn gradient_noise(x: f64, y: f64, period: i64, seed: u64) -> f64 {
+	let (ix, iy) = (x.floor() as i64, y.floor() as i64);
+	let (fx, fy) = (x - ix as f64, y - iy as f64);
+	let dot = |cx: i64, cy: i64| {
+		let a = 2.0 * PI * lattice(ix + cx, iy + cy, period, seed).0;
+		a.cos() * (fx - cx as f64) + a.sin() * (fy - cy as f64)
+	};
+	let smooth = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+	let (sx, sy) = (smooth(fx), smooth(fy));
+	let top = dot(0, 0) * (1.0 - sx) + dot(1, 0) * sx;
+	let bottom = dot(0, 1) * (1.0 - sx) + dot(1, 1) * sx;
+	top * (1.0 - sy) + bottom * sy
+}
+
+fn worley(u: f64, v: f64, cells: u32, seed: u64) -> f64 {
+	let n = cells.max(1) as i64;
+	let (x, y) = (u * n as f64, v * n as f64);
+	let (ix, iy) = (x.floor() as i64, y.floor() as i64);
+	let mut nearest = INF;
+	for cy in iy - 1..=iy + 1 {
+		for cx in ix - 1..=ix + 1 {
+			let (px, py) = lattice(cx, cy, n, seed);
+			let (dx, dy) = (cx as f64 + px - x, cy as f64 + py - y);
+			nearest = nearest.min((dx * dx + dy * dy).sqrt());
+		}
+	}
+	clip01(nearest)
+}
+
+fn bricks(u: f64, v: f64, rows: u32, cols: u32, mortar: f64, bevel: f64) -> f64 {
+	let (rows, cols) = (rows.max(1) as f64, cols.max(1) as f64);
+	let row = (v * rows).floor();
+	let shift = if row as i64 % 2 == 1 { 0.5 } else { 0.0 };
+	let fx = fract(u * cols + shift);
+	let fy = fract(v * rows);
+	// distance to the nearest brick edge, in tile units.
+	let d = min(min(fx, 1.0 - fx) / cols, min(fy, 1.0 - fy) / rows);
+	ramp(d - mortar / 2.0, bevel)
+}
+
+#[test]
+fn test_patterns() {
+	use Pattern::*;
+	let flat = Recipe {
+		height: Const(0.5),
+		diffuse: None,
+	};
+	let hm = flat.height_map(4);
+	assert_eq!(hm.dimensions(), (4, 4));
+	assert_eq!(hm.at((1, 2)), 128);
+	assert!(flat.diffuse_map(4).is_none());
+
+	// rivet (and the old "sphere"): top in the center, 0 outside the radius.
+	let r = Rivets { n: 1, radius: 0.25 };
+	assert_eq!(r.at(0.5, 0.5), 1.0);
+	assert_eq!(r.at(0.1, 0.5), 0.0);
+
+	let b = Bricks {
+		rows: 2,
+		cols: 1,
+		mortar: 0.1,
+		bevel: 0.1,
+	};
+	assert_eq!(b.at(0.5, 0.25), 1.0); // center of a brick
+	assert_eq!(b.at(0.5, 0.5), 0.0); // mortar between rows
+	assert_eq!(b.at(0.5, 0.75), 0.0); // head joint, shifted to the middle on odd rows
+	assert_eq!(b.at(0.0, 0.75), 1.0);
+	assert!(b.at(0.25, 0.6) > 0.0 && b.at(0.25, 0.6) < 1.0); // bevel
+
+	assert_eq!(Bevel(0.25).at(0.125, 0.5), 0.5);
+	assert_eq!(Min(vec![Const(0.2), Const(0.7)]).at(0.0, 0.0), 0.2);
+	assert_eq!(Max(vec![Const(0.2), Const(0.7)]).at(0.0, 0.0), 0.7);
+	let blend = Blend {
+		a: Box::new(Const(0.0)),
+		b: Box::new(Const(1.0)),
+		t: 0.25,
+	};
+	assert_eq!(blend.at(0.0, 0.0), 0.25);
+	let scale = Scale {
+		a: Box::new(Const(0.5)),
+		mul: -1.0,
+		add: 1.0,
+	};
+	assert_eq!(scale.at(0.0, 0.0), 0.5);
+}
+
+#[test]
+fn test_noise_tiles() {
+	use Pattern::*;
+	let noise = [
+		Perlin {
+			cells: 3,
+			octaves: 3,
+			seed: 7,
+		},
+		Worley { cells: 4, seed: 7 },
+	];
+	for h in &noise {
+		// seamless across the tile edges, deterministic, in range and not constant.
+		for &t in &[0.1, 0.37, 0.8] {
+			let eps = 1e-9;
+			assert!((h.at(eps, t) - h.at(1.0 - eps, t)).abs() < 1e-6);
+			assert!((h.at(t, eps) - h.at(t, 1.0 - eps)).abs() < 1e-6);
+		}
+		let values: Vec<f64> = (0..100).map(|i| h.at(i as f64 / 100.0, 0.3)).collect();
+		assert!(values.iter().all(|x| (0.0..=1.0).contains(x)));
+		assert!(values.iter().any(|&x| (x - values[0]).abs() > 0.1));
+		assert_eq!(values[17], h.at(0.17, 0.3));
+	}
+}
+
+#[test]
+fn test_check_perlin() {
+	use Pattern::*;
+	let perlin = |cells, octaves| Perlin {
+		cells,
+		octaves,
+		seed: 1,
+	};
+	assert!(perlin(3, 3).check().is_ok());
+	assert!(perlin(1, 25).check().is_ok());
+	assert!(perlin(1, 26).check().is_err());
+	assert!(perlin(3, 64).check().is_err());
+	assert!(perlin(u32::MAX, 1).check().is_err());
+	// nested in an operator, or selecting the diffuse color.
+	assert!(Max(vec![Const(0.0), perlin(2, 100)]).check().is_err());
+	let recipe = Recipe {
+		height: Const(0.5),
+		diffuse: Some(Gradient {
+			low: [0, 0, 0],
+			high: [255, 255, 255],
+			by: Some(perlin(1 << 20, 8)),
+		}),
+	};
+	assert!(recipe.check().is_err());
+}
+
+#[test]
+fn test_recipe_json() {
+	let json = r#"{
+		"plate": {
+			"height": {"Max": [{"Bevel": 0.1}, {"Rivets": {"n": 2, "radius": 0.05}}]},
+			"diffuse": {"low": [0, 0, 0], "high": [200, 100, 50]}
+		},
+		"rock": {"height": {"Worley": {"cells": 4, "seed": 1}}}
+	}"#;
+	let recipes: BTreeMap<String, Recipe> = serde_json::from_str(json).unwrap();
+	assert_eq!(recipes.keys().collect::<Vec<_>>(), vec!["plate", "rock"]);
+	assert!(recipes["rock"].diffuse.is_none());
+
+	let plate = &recipes["plate"];
+	let dm = plate.diffuse_map(8).unwrap();
+	// plate (height 1) is colored high, the edges low.
+	assert_eq!(dm.at((3, 2)), BGRA(50, 100, 200, 255));
+	assert_eq!(
+		plate.height_map(8).at((0, 3)),
+		to_u8(Pattern::Bevel(0.1).at(0.0625, 0.4375))
+	);
+}
@@ -1,21 +1,31 @@
 use crate::prelude::*;
 extern crate num_cpus;
-extern crate rand;
-use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicI64;
 use std::sync::atomic::Ordering::SeqCst;
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::thread::spawn;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 /// Caching ray tracer.
 pub struct Renderer {
 	cache: FnvHashMap<TileKey, Rc<Texture>>,
 	baking: FnvHashSet<TileKey>,
 	empty: Rc<Texture>,
+	// number of tiles that failed to bake.
+	errors: usize,
+	// tiles cached as error_image after a timeout, whose late result may still replace it.
+	late: FnvHashSet<TileKey>,
 
 	palette: Arc<Palette>,
 
+	// lights as stored in the level,
+	// the bakery renders with these scaled to the light quality.
+	lights: Lights,
+	quality: Quality,
+
 	/// Worker pool for async ray-tracing
 	bakery: Bakery,
 }
@@ -23,22 +33,65 @@ pub struct Renderer {
 // The palette maps block id's (0-225, elements of a Map) to 3D surfaces.
 type Palette = Vec<Surface>;
 
+/// How a Renderer bakes tiles (e.g. set with the --threads and --quality flags).
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct RenderOptions {
+	/// Number of worker threads, 0 means one per core but one.
+	pub threads: usize,
+	pub quality: Quality,
+}
+
 ////////////////////////////////////////////////////////////////////////////////////  This is the caching part
 
 impl Renderer {
 	pub fn new(palette: Vec<Surface>, lights: Lights) -> Self {
+		Self::with_options(palette, lights, RenderOptions::default())
+	}
+
+	pub fn with_options(palette: Vec<Surface>, lights: Lights, opts: RenderOptions) -> Self {
 		let palette = Arc::new(palette);
 		Renderer {
 			cache: FnvHashMap::default(),
 			baking: FnvHashSet::default(),
-			bakery: Bakery::new(palette.clone(), lights),
+			bakery: Bakery::new(
+				palette.clone(),
+				lights.with_quality(opts.quality),
+				opts.threads,
+			),
 			palette,
+			lights,
+			quality: opts.quality,
 			empty: Rc::new(Texture::default()),
+			errors: 0,
+			late: FnvHashSet::default(),
 		}
 	}
 
 	pub fn lights(&self) -> Lights {
-		self.bakery.shared_data.lights.clone()
+		self.lights.clone()
+	}
+
+	/// Like render_tile, but blocks until all tiles are fully baked.
+	/// The tiles are baked in parallel. Used to render whole levels offline.
+	pub fn render_tiles_blocking(&mut self, tilekeys: &[TileKey]) -> Vec<Rc<Texture>> {
+		let tilekeys: Vec<TileKey> = tilekeys.iter().map(|&k| self.canonicalize(k)).collect();
+		// too few tiles to keep all workers busy: split them.
+		let urgent = tilekeys.len() < self.bakery.pool.num_workers();
+		for &k in &tilekeys {
+			if !self.cache.contains_key(&k) && !self.is_baking(k) {
+				self.start_baking(k, urgent);
+			}
+		}
+		tilekeys
+			.iter()
+			.map(|&k| match self.cache.get(&k) {
+				Some(tex) => tex.clone(),
+				None => match self.bakery.recv(k) {
+					Ok(img) => self.create(k, img),
+					Err(e) => self.failed(k, e),
+				},
+			})
+			.collect()
 	}
 
 	/// Renders and returns the Texture for the central tile in Tilekey.
@@ -52,6 +105,11 @@ impl Renderer {
 		// ignore neighboaring surfaces that cannot throw a shadow.
 		let tilekey = self.canonicalize(tilekey);
 
+		// timed out earlier: the result may have come in since.
+		if self.late.contains(&tilekey) {
+			self.try_recv_late(tilekey);
+		}
+
 		// already baked
 		if let Some(tex) = self.cache.get(&tilekey) {
 			return tex.clone();
@@ -66,7 +124,7 @@ impl Renderer {
 
 		// not yet started: start baking
 		if !self.is_baking(tilekey) {
-			self.start_baking(tilekey);
+			self.start_baking(tilekey, false);
 		}
 
 		// the requested texture is not availbe yet
@@ -80,23 +138,80 @@ impl Renderer {
 		}
 	}
 
+	/// Bake with other lights from now on (e.g. edited in the level file).
+	/// All tiles depend on the lights: the cache is cleared and bakes in progress are cancelled.
+	pub fn set_lights(&mut self, lights: Lights) {
+		self.lights = lights;
+		self.invalidate(|_| true);
+	}
+
+	/// Replace the surface of block id blk (e.g. after its textures changed on disk).
+	/// Only the tiles showing blk are dropped from the cache, or cancelled if being baked.
+	/// If blk's height range changed, so are the tiles that blk can now shadow:
+	/// canonicalize may have dropped blk from their keys.
+	pub fn set_surface(&mut self, blk: u8, surface: Surface) {
+		let old = &self.palette[blk as usize];
+		let heights = |s: &Surface| (s.hm_min(), s.hm_max());
+		let moved = heights(old) != heights(&surface);
+		Arc::make_mut(&mut self.palette)[blk as usize] = surface;
+
+		let shadows: Vec<bool> = (0..self.palette.len())
+			.map(|c| moved && !self.is_below(blk, c as u8))
+			.collect();
+		self.invalidate(|k| k.uses(blk) || shadows[k.center() as usize]);
+	}
+
+	// Drop the cached tiles, and cancel the bakes, of TileKeys for which f returns true.
+	// From now on, the bakery uses the current palette and lights.
+	fn invalidate(&mut self, f: impl Fn(&TileKey) -> bool) {
+		self.cache.retain(|k, _| !f(k));
+		self.baking.retain(|k| !f(k));
+		self.late.retain(|k| !f(k));
+		let lights = self.lights.with_quality(self.quality);
+		self.bakery.reset(self.palette.clone(), lights, f);
+	}
+
+	/// Like render_tile, but if the tile is not yet baking,
+	/// bake it before all other tiles, split over all render threads.
+	/// E.g. for a tile that was just edited.
+	pub fn render_tile_urgent(&mut self, tilekey: TileKey) -> Rc<Texture> {
+		let k = self.canonicalize(tilekey);
+		if !self.cache.contains_key(&k) && !self.is_baking(k) {
+			self.start_baking(k, true);
+		}
+		self.render_tile(k)
+	}
+
 	/// Replace TileKey by the simplest TileKey that will render into the same result,
 	/// by removing neighboring blocks that can never cast a shadow on the centeral block
-	/// because they have strictly lower height.
+	/// because they have strictly lower height, or are outside of the Lights' neighborhood.
 	///
 	/// This significantly reduces the number of tiles to render and keep in memory.
 	fn canonicalize(&self, k: TileKey) -> TileKey {
 		let c = k.center();
+		let n = self.lights.neighborhood;
+		// the denoise margin shows the blocks next to the central one.
+		let shown = if self.lights.denoise.radius() > 0 {
+			1
+		} else {
+			0
+		};
 		let mut k = k.clone();
-		k.blocks[0][0] = self.canonicalize1(k.blocks[0][0], c);
-		k.blocks[0][1] = self.canonicalize1(k.blocks[0][1], c);
-		k.blocks[0][2] = self.canonicalize1(k.blocks[0][2], c);
-		k.blocks[1][0] = self.canonicalize1(k.blocks[1][0], c);
-		k.blocks[1][2] = self.canonicalize1(k.blocks[1][2], c);
-		k.blocks[2][0] = self.canonicalize1(k.blocks[2][0], c);
-		k.blocks[2][1] = self.canonicalize1(k.blocks[2][1], c);
-		k.blocks[2][2] = self.canonicalize1(k.blocks[2][2], c);
-
+		for (y, row) in k.blocks.iter_mut().enumerate() {
+			for (x, blk) in row.iter_mut().enumerate() {
+				if (x, y) == (TILE_KEY_RADIUS, TILE_KEY_RADIUS) {
+					continue;
+				}
+				let dist = max(x.abs_diff(TILE_KEY_RADIUS), y.abs_diff(TILE_KEY_RADIUS));
+				*blk = if dist > n {
+					0
+				} else if dist <= shown {
+					*blk
+				} else {
+					self.canonicalize1(*blk, c)
+				};
+			}
+		}
 		k
 	}
 
@@ -114,9 +229,12 @@ impl Renderer {
 		self.palette[surfa as usize].hm_max() <= self.palette[surfb as usize].hm_min()
 	}
 
-	fn start_baking(&mut self, tilekey: TileKey) {
+	fn start_baking(&mut self, tilekey: TileKey, urgent: bool) {
 		self.baking.insert(tilekey); // mark baking
-		self.bakery.send(tilekey);
+		match urgent {
+			true => self.bakery.send_urgent(tilekey),
+			false => self.bakery.send(tilekey),
+		}
 	}
 
 	fn is_baking(&self, tilekey: TileKey) -> bool {
@@ -126,7 +244,35 @@ impl Renderer {
 	fn try_recv(&mut self, tilekey: TileKey) -> Option<Rc<Texture>> {
 		match self.bakery.try_recv(tilekey) {
 			None => None,
-			Some(img) => Some(self.create(tilekey, img)),
+			Some(Ok(img)) => Some(self.create(tilekey, img)),
+			Some(Err(e)) => Some(self.failed(tilekey, e)),
+		}
+	}
+
+	// Report a tile that could not be baked, and cache an error texture in its place
+	// (so that it is not baked again and again).
+	// A tile that timed out is still being baked: its late result replaces the error texture.
+	fn failed(&mut self, tilekey: TileKey, e: Error) -> Rc<Texture> {
+		eprintln!("error baking {:?}: {}", tilekey, e);
+		self.errors += 1;
+		if self.bakery.baking.contains_key(&tilekey) {
+			self.late.insert(tilekey);
+		}
+		self.create(tilekey, error_image())
+	}
+
+	fn try_recv_late(&mut self, tilekey: TileKey) {
+		match self.bakery.try_recv(tilekey) {
+			None => (),
+			Some(Ok(img)) => {
+				self.late.remove(&tilekey);
+				self.errors -= 1;
+				self.create(tilekey, img);
+			}
+			Some(Err(_)) => {
+				// panicked after all: keep the error texture.
+				self.late.remove(&tilekey);
+			}
 		}
 	}
 
@@ -137,6 +283,17 @@ impl Renderer {
 	}
 
 	// -------------------------------------------------------------------------------- debug
+	pub fn stats(&self) -> RenderStats {
+		let util = self.bakery.pool.utilization();
+		RenderStats {
+			queued: self.bakery.baking.len(),
+			cached: self.cache.len(),
+			queue_depth: self.bakery.pool.queue_depth(),
+			utilization: util.iter().sum::<f64>() / util.len() as f64,
+			errors: self.errors,
+		}
+	}
+
 	pub fn print_stats(&self) {
 		println!(
 			"texture_manager: baking: {}, inuse: {}",
@@ -147,11 +304,44 @@ impl Renderer {
 	}
 }
 
-/// A 3x3 piece of a Map.
-/// Bakery can render the central block, considering shadows from its neighbors.
-#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+/// Renderer statistics for the debug overlay.
+#[derive(Copy, Clone, Debug)]
+pub struct RenderStats {
+	/// Tiles sent to the bakery, not yet received back.
+	pub queued: usize,
+	/// Tiles baked and cached.
+	pub cached: usize,
+	/// Render jobs (tiles or parts of tiles) not yet started.
+	pub queue_depth: usize,
+	/// Average fraction of time the render threads were busy, over the last second or so.
+	pub utilization: f64,
+	/// Tiles that failed to bake, shown as error_image.
+	pub errors: usize,
+}
+
+/// Shown in place of a tile that failed to bake: a magenta and black checkerboard.
+pub fn error_image() -> Image<BGRA> {
+	let w = GRID as i32;
+	let s = w / 8;
+	Image::from_fn((w, w), |x, y| {
+		if (x / s + y / s) % 2 == 0 {
+			BGRA(255, 0, 255, 255)
+		} else {
+			BGRA(0, 0, 0, 255)
+		}
+	})
+}
+
+/// Number of neighbors on each side of the central block in a TileKey.
+pub const TILE_KEY_RADIUS: usize = 2;
+const TILE_KEY_SIZE: usize = 2 * TILE_KEY_RADIUS + 1;
+
+/// A 5x5 piece of a Map.
+/// Bakery can render the central block, considering shadows from its neighbors
+/// up to Lights::neighborhood blocks away.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct TileKey {
-	pub blocks: [[u8; 3]; 3],
+	pub blocks: [[u8; TILE_KEY_SIZE]; TILE_KEY_SIZE],
 	pub goody: u8,
 }
 
@@ -160,27 +350,40 @@ impl TileKey {
 		Self::with_center(0)
 	}
 	pub fn with_center(block: u8) -> Self {
-		TileKey {
-			blocks: [[0, 0, 0], [0, block, 0], [0, 0, 0]],
+		let mut k = TileKey {
+			blocks: [[0; TILE_KEY_SIZE]; TILE_KEY_SIZE],
 			goody: 0,
-		}
+		};
+		k.blocks[TILE_KEY_RADIUS][TILE_KEY_RADIUS] = block;
+		k
 	}
 	pub fn center(self) -> u8 {
-		self.blocks[1][1]
+		self.blocks[TILE_KEY_RADIUS][TILE_KEY_RADIUS]
+	}
+	/// Does the tile show block blk (in the neighborhood or as goody)?
+	pub fn uses(self, blk: u8) -> bool {
+		self.goody == blk || self.blocks.iter().flatten().any(|&b| b == blk)
+	}
+	/// Block at offset (dx, dy) from the center, with dx, dy between -TILE_KEY_RADIUS and +TILE_KEY_RADIUS.
+	pub fn at(self, dx: i32, dy: i32) -> u8 {
+		let r = TILE_KEY_RADIUS as i32;
+		self.blocks[(dy + r) as usize][(dx + r) as usize]
+	}
+	pub fn set(&mut self, dx: i32, dy: i32, blk: u8) {
+		let r = TILE_KEY_RADIUS as i32;
+		self.blocks[(dy + r) as usize][(dx + r) as usize] = blk;
 	}
 	//fn center_only(self) -> Self {
 	//	Self::with_center(self.center())
 	//}
 	fn center_and_goody(self) -> Self {
 		TileKey {
-			blocks: [[0, 0, 0], [0, self.center(), 0], [0, 0, 0]],
 			goody: self.goody,
+			..Self::with_center(self.center())
 		}
 	}
 	fn is_center_only(self) -> bool {
-		let mut masked = self;
-		masked.blocks[1][1] = 0;
-		masked.blocks == Self::new().blocks
+		self.blocks == Self::with_center(self.center()).blocks
 	}
 	//fn center_empty(&self) -> bool {
 	//	self.blocks[1][1] == 0 && self.goody == 0
@@ -188,93 +391,264 @@ impl TileKey {
 }
 
 /// Bakery asynchronously renders ("bakes") lighting effects.
+///
+/// A panic while baking a tile, or a tile taking longer than BAKE_TIMEOUT,
+/// is reported as an error for that tile. Dropping the Bakery stops and joins the workers.
 struct Bakery {
-	to_work: Sender<TileKey>,
-	from_work: Receiver<DoneItem>,
+	pool: Pool,
+	to_bakery: Sender<Msg>,
+	from_work: Receiver<Msg>,
 	shared_data: Arc<SharedData>,
-	outbox: FnvHashMap<TileKey, Image<BGRA>>,
-	num_baking: i32,
+	// tiles sent but not yet in the outbox.
+	baking: FnvHashMap<TileKey, Baking>,
+	next_id: u64,
+	outbox: FnvHashMap<TileKey, Baked>,
+	// urgent tiles, baked in bands of rows: rows received so far, and how many.
+	partial: FnvHashMap<TileKey, (Image<BGRA>, i32)>,
+	// BAKE_TIMEOUT, shorter in tests.
+	timeout: Duration,
 }
 
-/// TileKey + rendered image, sent back by worker threads.
-type DoneItem = (TileKey, Image<BGRA>);
+/// A tile sent to the Bakery.
+struct Baking {
+	// tells this bake apart from earlier, cancelled, bakes of the same TileKey.
+	id: u64,
+	// when a worker started baking (None while queued).
+	started: Option<Instant>,
+	// set when cancelled, so that queued jobs are skipped.
+	cancelled: Arc<AtomicBool>,
+	// the timeout was reported, but a late result is still accepted.
+	timed_out: bool,
+}
+
+/// A baked image, or why baking failed.
+/// (Not Result, as errors are sent between threads).
+type Baked = std::result::Result<Image<BGRA>, String>;
+
+/// Sent back by worker threads.
+enum Msg {
+	/// A worker started baking (part of) a tile, with given Baking id.
+	Started(TileKey, u64),
+	/// Rows of a tile, starting at the given row (usually the whole tile).
+	Done(TileKey, u64, i32, Baked),
+}
+
+/// Tiles taking longer than this (once started) are reported as failed,
+/// until their late result comes in.
+const BAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// The message of a panic, as passed to catch_unwind.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+	match payload.downcast::<String>() {
+		Ok(msg) => *msg,
+		Err(payload) => match payload.downcast::<&str>() {
+			Ok(msg) => msg.to_string(),
+			Err(_) => "panic while baking".into(),
+		},
+	}
+}
 
 /// Read-only data needed by worker threads for rendering.
 /// TODO: pub only for Editor.
 pub struct SharedData {
 	palette: Arc<Palette>,
 	lights: Lights,
+	marcher: Marcher,
 	cpu_millis: AtomicI64,
 }
 
 ////////////////////////////////////////////////////////////////////////////////////  This is the async part
 
 impl Bakery {
-	fn new(palette: Arc<Palette>, lights: Lights) -> Self {
-		let shared_data = Arc::new(SharedData::new(palette, lights));
-		let (to_work, from_bakery) = mpmc_channel::<TileKey>();
-		let (to_bakery, from_work) = channel::<DoneItem>();
-
-		for _i in 0..Self::num_render_threads() {
-			// thread-local Arc/channel clones
-			let shared_data = Arc::clone(&shared_data);
-			let from_bakery = from_bakery.clone();
-			let to_bakery = to_bakery.clone();
-			spawn(move || {
-				for tilekey in from_bakery {
-					let img = shared_data.render_central_block(tilekey);
-					if to_bakery.send((tilekey, img)).is_err() {
-						break;
-					}
-				}
-			});
-		}
-
+	// threads: number of workers, 0 means automatic.
+	fn new(palette: Arc<Palette>, lights: Lights, threads: usize) -> Self {
+		let (to_bakery, from_work) = channel::<Msg>();
 		Self {
-			to_work,
+			pool: Pool::new(Self::num_render_threads(threads)),
+			to_bakery,
 			from_work,
-			shared_data,
+			shared_data: Arc::new(SharedData::new(palette, lights)),
+			baking: FnvHashMap::default(),
+			next_id: 0,
 			outbox: FnvHashMap::default(),
-			num_baking: 0,
+			partial: FnvHashMap::default(),
+			timeout: BAKE_TIMEOUT,
 		}
 	}
 
-	fn num_render_threads() -> usize {
-		// number of cores - 1 (to leave one free for rendering etc)
-		// but no less than 1 core, of course.
-		max(num_cpus::get() - 1, 1)
+	fn num_render_threads(threads: usize) -> usize {
+		match threads {
+			// number of cores - 1 (to leave one free for rendering etc)
+			// but no less than 1 core, of course.
+			0 => max(num_cpus::get() - 1, 1),
+			n => n,
+		}
 	}
 
 	/// Send work to the Bakery: start asynchronously rendering
 	/// TileKey's central tile. The baked image can later be retrieved
 	/// through recv() or try_recv().
 	fn send(&mut self, tilekey: TileKey) {
-		self.num_baking += 1;
-		self.to_work.send(tilekey).unwrap();
+		self.start(tilekey);
+		self.pool.spawn(self.job(tilekey, 0, GRID as i32));
+	}
+
+	/// Like send, but bake TileKey before all other tiles,
+	/// split in bands of rows so that all render threads work on it.
+	fn send_urgent(&mut self, tilekey: TileKey) {
+		self.start(tilekey);
+		let w = GRID as i32;
+		let n = self.pool.num_workers() as i32;
+		let rows = (w + n - 1) / n;
+		for y0 in (0..w).step_by(rows as usize) {
+			self.pool
+				.spawn_urgent(self.job(tilekey, y0, min(y0 + rows, w)));
+		}
+	}
+
+	fn start(&mut self, tilekey: TileKey) {
+		self.next_id += 1;
+		let baking = Baking {
+			id: self.next_id,
+			started: None,
+			cancelled: Arc::new(AtomicBool::new(false)),
+			timed_out: false,
+		};
+		self.baking.insert(tilekey, baking);
+	}
+
+	// Work for the pool: bake rows y0..y1 of a tile (just sent), turning a panic into an error.
+	fn job(&self, tilekey: TileKey, y0: i32, y1: i32) -> impl FnOnce() + Send {
+		let (shared_data, to_bakery) = (self.shared_data.clone(), self.to_bakery.clone());
+		let (id, cancelled) = {
+			let b = &self.baking[&tilekey];
+			(b.id, b.cancelled.clone())
+		};
+		move || {
+			if cancelled.load(SeqCst) {
+				return;
+			}
+			let _ = to_bakery.send(Msg::Started(tilekey, id));
+			let baked = catch_unwind(AssertUnwindSafe(|| {
+				shared_data.render_rows(tilekey, y0, y1)
+			}))
+			.map_err(panic_message);
+			let _ = to_bakery.send(Msg::Done(tilekey, id, y0, baked));
+		}
+	}
+
+	/// Cancel the tiles for which cancel returns true (baking or in the outbox),
+	/// and bake with a new palette and lights from now on.
+	fn reset(&mut self, palette: Arc<Palette>, lights: Lights, cancel: impl Fn(&TileKey) -> bool) {
+		for (_, b) in self.baking.iter().filter(|(k, _)| cancel(k)) {
+			b.cancelled.store(true, SeqCst);
+		}
+		self.baking.retain(|k, _| !cancel(k));
+		self.partial.retain(|k, _| !cancel(k));
+		self.outbox.retain(|k, _| !cancel(k));
+
+		let shared_data = SharedData::new(palette, lights);
+		let cpu_millis = self.shared_data.cpu_millis.load(SeqCst);
+		shared_data.cpu_millis.store(cpu_millis, SeqCst);
+		self.shared_data = Arc::new(shared_data);
 	}
 
 	/// Return the rendered image corresponding to TileKey if ready, None otherwise.
+	/// Returns an error if baking panicked, or did not finish within BAKE_TIMEOUT.
+	/// A tile that timed out keeps baking: try_recv returns None until its late result is in.
 	/// The TileKey must have been sent(), exactly once, earlier.
-	///
-	/// TODO: panic if TileKey was not sent earlier, instead of perpetually returning None.
-	fn try_recv(&mut self, tilekey: TileKey) -> Option<Image<BGRA>> {
+	fn try_recv(&mut self, tilekey: TileKey) -> Option<Result<Image<BGRA>>> {
 		// move completed items to outbox, if any.
-		for item in self.from_work.try_iter() {
-			self.num_baking -= 1;
-			self.outbox.insert(item.0, item.1);
+		while let Ok(msg) = self.from_work.try_recv() {
+			self.accept(msg);
 		}
 		// return item from outbox, if present.
-		self.outbox.remove(&tilekey)
+		if let Some(baked) = self.outbox.remove(&tilekey) {
+			return Some(baked.or_else(GenError::new));
+		}
+		let timeout = self.timeout;
+		match self.baking.get_mut(&tilekey) {
+			None => panic!("Bakery::try_recv: {:?} was not sent", tilekey),
+			Some(b) if !b.timed_out && b.started.is_some_and(|s| s.elapsed() > timeout) => {
+				b.timed_out = true;
+				Some(GenError::new(format!(
+					"timed out after {:.1} s",
+					timeout.as_secs_f64()
+				)))
+			}
+			Some(_) => None,
+		}
+	}
+
+	/// Like try_recv, but blocks until the image is ready (or failed).
+	fn recv(&mut self, tilekey: TileKey) -> Result<Image<BGRA>> {
+		loop {
+			if let Some(result) = self.try_recv(tilekey) {
+				return result;
+			}
+			// wake up now and then to check the timeout.
+			if let Ok(msg) = self.from_work.recv_timeout(Duration::from_millis(100)) {
+				self.accept(msg);
+			}
+		}
+	}
+
+	// Move a completed item to the outbox,
+	// or, for a band of rows, to its partial tile until that is complete.
+	fn accept(&mut self, msg: Msg) {
+		let (tilekey, id, y0, baked) = match msg {
+			Msg::Started(tilekey, id) => {
+				if let Some(b) = self.baking.get_mut(&tilekey) {
+					if b.id == id && b.started.is_none() {
+						b.started = Some(Instant::now());
+					}
+				}
+				return;
+			}
+			Msg::Done(tilekey, id, y0, baked) => (tilekey, id, y0, baked),
+		};
+		match self.baking.get(&tilekey) {
+			Some(b) if b.id == id => (),
+			_ => return, // failed or cancelled earlier.
+		}
+		let w = GRID as i32;
+		let baked = match baked {
+			Ok(img) if img.dimensions().1 != w => {
+				let (mut tile, rows) = self
+					.partial
+					.remove(&tilekey)
+					.unwrap_or_else(|| (Image::new((w, w)), 0));
+				tile.paste((0, y0), &img);
+				let rows = rows + img.dimensions().1;
+				if rows < w {
+					self.partial.insert(tilekey, (tile, rows));
+					return;
+				}
+				Ok(tile)
+			}
+			baked => baked,
+		};
+		self.partial.remove(&tilekey);
+		self.baking.remove(&tilekey);
+		self.outbox.insert(tilekey, baked);
 	}
 
 	// ------------------------------------------------------------------------- debug
 	fn print_stats(&self) {
 		let cpusecs = self.shared_data.cpu_millis.load(SeqCst) as f64 / 1000.0;
+		let util: Vec<String> = self
+			.pool
+			.utilization()
+			.iter()
+			.map(|u| format!("{:.0}%", 100.0 * u))
+			.collect();
 		println!(
-			"bakery: baking: {}, outbox: {}, CPU: {} s",
-			self.num_baking,
+			"bakery: baking: {}, queued: {}, outbox: {}, CPU: {} s, utilization: {}",
+			self.baking.len(),
+			self.pool.queue_depth(),
 			self.outbox.len(),
 			cpusecs,
+			util.join(" "),
 		);
 	}
 }
@@ -286,105 +660,135 @@ impl SharedData {
 		Self {
 			palette,
 			lights,
+			marcher: Marcher::default(),
 			cpu_millis: AtomicI64::new(0),
 		}
 	}
 
+	/// Trace shadow rays with the given Marcher (default: Packet).
+	/// Used to compare against the Scalar reference implementation.
+	pub fn set_marcher(&mut self, m: Marcher) {
+		self.marcher = m;
+	}
+
 	pub fn render_central_block(&self, chunk: TileKey) -> Image<BGRA> {
+		self.render_rows(chunk, 0, GRID as i32)
+	}
+
+	/// Render only rows y0..y1 of the central block,
+	/// so that one tile can be split over several threads.
+	/// The result is identical to the corresponding rows of render_central_block.
+	pub fn render_rows(&self, chunk: TileKey, y0: i32, y1: i32) -> Image<BGRA> {
 		let start = Instant::now();
 		let w = GRID as i32;
-		let img = Image::from_fn((w, w), |x, y| self.shade_pix(chunk, Int2(x, y)));
+		let hf = match self.marcher {
+			Marcher::Scalar => None,
+			Marcher::Packet => Some(self.height_field(chunk)),
+		};
+		let hf = hf.as_ref();
+		let img = match self.lights.denoise.radius() {
+			0 => Image::from_fn((w, y1 - y0), |x, y| {
+				self.shade_pix(chunk, hf, Int2(x, y0 + y))
+			}),
+			r => self.render_denoised(chunk, hf, r, y0, y1),
+		};
 		self.cpu_millis
 			.fetch_add(start.elapsed().as_millis() as i64, SeqCst);
 		img
 	}
 
-	fn shade_pix(&self, chunk: TileKey, pix: Int2) -> BGRA {
-		// TODO: not correct w/ goodies
-		// TODO: method: empty()
-		//if chunk.blocks[1][1] == 0 {
-		//	return BGRA(0, 0, 0, 0);
-		//}
+	// Like render_central_block, but with the lighting term denoised before applying the diffuse map.
+	// Lighting is also computed on a margin of r pixels around the central block,
+	// using the neighboring blocks, so that the filter does not stop (or blur) at the tile's border.
+	// The margin is lit with this tile's samples (see scramble), not those of the adjacent tile,
+	// so the noise left after filtering still differs slightly across the seam.
+	fn render_denoised(
+		&self,
+		chunk: TileKey,
+		hf: Option<&HeightField>,
+		r: i32,
+		y0: i32,
+		y1: i32,
+	) -> Image<BGRA> {
+		let w = GRID as i32;
+		let pix = |x: i32, y: i32| Int2(x - r, y0 + y - r);
+		let dim = (w + 2 * r, (y1 - y0) + 2 * r);
+		let normal = Image::from_fn(dim, |x, y| self.normal_at(chunk, pix(x, y)));
+		let height = Image::from_fn(dim, |x, y| self.height_at(chunk, pix(x, y)));
+		let light = Image::from_fn(dim, |x, y| {
+			let p = (x, y);
+			self.light_at(chunk, hf, pix(x, y), normal.at(p), height.at(p))
+		});
+		let light = denoise(&light, &normal, &height, r);
+		Image::from_fn((w, y1 - y0), |x, y| {
+			self.diffuse(chunk, Int2(x, y0 + y)).lit(light.at((x, y)))
+		})
+	}
 
-		//let centre_surf = &self.palette[chunk.center() as usize];
+	fn shade_pix(&self, chunk: TileKey, hf: Option<&HeightField>, pix: Int2) -> BGRA {
+		let normal = self.normal_at(chunk, pix);
+		let z = self.height_at(chunk, pix);
+		let light = self.light_at(chunk, hf, pix, normal, z);
+		self.diffuse(chunk, pix).lit(light)
+	}
 
-		//let mut dm = centre_surf.diffuse_at(pix);
+	// diffuse color of the central block (and goody), possibly inverted.
+	fn diffuse(&self, chunk: TileKey, pix: Int2) -> BGRA {
+		// TODO: not correct w/ goodies
 		let mut dm = self.diffuse_at(chunk, pix);
-		//if dm.a() == 0 {
-		//	return BGRA(0, 0, 0, 0);
-		//}
-
 		if self.lights.invert_dm {
 			dm.0 = 255 - dm.0;
 			dm.1 = 255 - dm.1;
 			dm.2 = 255 - dm.2;
 		}
+		dm
+	}
 
-		//let normal = centre_surf.normal_at(pix);
-		let normal = self.normal_at(chunk, pix);
-
-		let xy = Self::to_abs_pos(Usize2(1, 1), pix);
-		let z = self.height_at(chunk, pix);
-
+	// total incoming light at a pixel (with given surface normal and height).
+	// pix may lie outside of the central block (up to Lights::MAX_MARGIN pixels).
+	fn light_at(
+		&self,
+		chunk: TileKey,
+		hf: Option<&HeightField>,
+		pix: Int2,
+		normal: Vec3,
+		z: f64,
+	) -> RGBf {
+		let xy = Self::to_abs_pos(Usize2(TILE_KEY_RADIUS, TILE_KEY_RADIUS), pix);
 		let pos = Vector(xy.x(), xy.y(), z) + 0.02 * normal;
 
-		let mut rng = rand::thread_rng();
-		let rnd = (rng.gen::<f64>(), rng.gen::<f64>());
-
-		let ambient = self
-			.lights
-			.ambient
-			.mul(self.ambient_fraction(chunk, pos, normal, rnd) as f32);
-
-		let sunlight = self
-			.lights
-			.sun_intens
-			.mul(self.sun_fraction(chunk, pos, normal, rnd) as f32);
-
-		let total_light = ambient.add(&sunlight).add(&self.lights.fake_ambient);
-
-		let dml = dm.linear();
-		//let alpha = dm.a() as f32 / 255.0;
-		BGRA(
-			linear_to_srgb8(dml.b() * total_light.0),
-			linear_to_srgb8(dml.g() * total_light.1),
-			linear_to_srgb8(dml.r() * total_light.2),
-			dm.a(),
-		)
-	}
-
-	fn ambient_fraction(&self, chunk: TileKey, pos: Vec3, normal: Vec3, rand: (f64, f64)) -> f64 {
-		let mut total_light = 0.0;
-		let n = self.lights.ambient_rays;
-		for i in 0..n {
-			let (u, v) = halton23_scrambled(i, rand);
-			let dir = cosine_sphere((u, v), normal);
-			let r = Ray::new(pos, dir);
-			if !self.intersects(chunk, &r) {
-				total_light += 1.0 / (n as f64);
+		let rnd = self.scramble(chunk, pix);
+		self.lights.light_at(normal, rnd, |dirs, hit| match hf {
+			Some(hf) => hf.occluded(pos, dirs, hit),
+			None => {
+				for (h, &d) in hit.iter_mut().zip(dirs.iter()) {
+					*h = self.intersects(chunk, &Ray::new(pos, d));
+				}
 			}
-		}
-
-		total_light
+		})
 	}
 
-	fn sun_fraction(&self, chunk: TileKey, pos: Vec3, normal: Vec3, rand: (f64, f64)) -> f64 {
-		let mut total_light = 0.0;
-		let n = self.lights.sun_rays;
-		for i in 0..n {
-			let (u, v) = halton23_scrambled(i, rand);
-			let dir = self.lights.sample_sun_dir((u, v));
-			let r = Ray::new(pos, dir);
-			if !self.intersects(chunk, &r) {
-				total_light += re(normal.dot(dir)) / (n as f64);
-			}
+	// Scrambling of the light samples for a pixel:
+	// neighboring pixels get uncorrelated samples (noise instead of banding),
+	// but the same pixel of the same tile always gets the same.
+	fn scramble(&self, chunk: TileKey, pix: Int2) -> (f64, f64) {
+		// blocks + goody don't fit in one u64, hash them in parts of 8.
+		// (in a fixed array: this is called for every pixel).
+		const N: usize = 3 + (TILE_KEY_SIZE * TILE_KEY_SIZE + 1).div_ceil(8);
+		let mut values = [0u64; N];
+		values[..3].copy_from_slice(&[self.lights.seed, pix.0 as u64, pix.1 as u64]);
+		let bytes = chunk.blocks.iter().flatten().chain(Some(&chunk.goody));
+		for (i, &b) in bytes.enumerate() {
+			values[3 + i / 8] = values[3 + i / 8] << 8 | b as u64;
 		}
-		total_light
+		hash_scramble(&values)
 	}
 
+	// Scalar reference implementation of HeightField::occluded, used by Marcher::Scalar.
 	fn intersects(&self, chunk: TileKey, r: &Ray) -> bool {
-		debug_assert!(r.start.x() >= 0.9 && r.start.x() <= 2.1);
-		debug_assert!(r.start.y() >= 0.9 && r.start.y() <= 2.1);
+		let c = TILE_KEY_RADIUS as f64;
+		debug_assert!(r.start.x() >= c - 0.1 && r.start.x() <= c + 1.1);
+		debug_assert!(r.start.y() >= c - 0.1 && r.start.y() <= c + 1.1);
 		debug_assert!(r.start.z() >= 0.0 && r.start.z() <= 1.0);
 
 		// a ray pointing down will eventually hit something for sure.
@@ -399,18 +803,23 @@ impl SharedData {
 
 		let maxh = self.max_height(chunk);
 		assert!(maxh <= Surface::HM_MAX);
-		let n = GRID - 2;
-		for _i in 0..n {
+
+		// march until the ray leaves the neighborhood (sideways or above all blocks).
+		let n = self.lights.neighborhood as f64;
+		let (lo, hi) = (c - n, c + n + 1.0);
+		loop {
 			t += stride;
 			let p = r.at(t);
 			if p.z() > maxh {
 				return false;
 			}
+			if p.x() < lo || p.x() >= hi || p.y() < lo || p.y() >= hi {
+				return false;
+			}
 			if self.height_at_pos(chunk, p.xy()) > p.z() {
 				return true;
 			}
 		}
-		false
 	}
 
 	// maximum hight of all blocks in this tile.
@@ -427,6 +836,11 @@ impl SharedData {
 		mx
 	}
 
+	// Heights of the chunk's neighborhood, for the Packet marcher.
+	fn height_field(&self, chunk: TileKey) -> HeightField {
+		HeightField::new(self.lights.neighborhood, |p| self.height_at_pos(chunk, p))
+	}
+
 	fn to_abs_pos(tile: Usize2, pix: Int2) -> Vec2 {
 		let x = (tile.0 as f64) + (pix.0 as f64 / GRID as f64);
 		let y = (tile.1 as f64) + (pix.1 as f64 / GRID as f64);
@@ -461,6 +875,9 @@ impl SharedData {
 	}
 
 	fn normal_at(&self, chunk: TileKey, pix: Int2) -> Vec3 {
+		if let Some((surf, pix)) = self.neighbor_at(chunk, pix) {
+			return surf.normal_at(pix);
+		}
 		if chunk.goody != 0 {
 			let dm = self.palette[chunk.goody as usize].diffuse_at(pix);
 			if dm.a() != 0 {
@@ -474,6 +891,9 @@ impl SharedData {
 	}
 
 	fn height_at(&self, chunk: TileKey, pix: Int2) -> f64 {
+		if let Some((surf, pix)) = self.neighbor_at(chunk, pix) {
+			return surf.height_at(pix);
+		}
 		if chunk.goody != 0 {
 			let dm = self.palette[chunk.goody as usize].diffuse_at(pix);
 			if dm.a() != 0 {
@@ -485,13 +905,35 @@ impl SharedData {
 		// TODO: goodie.
 	}
 
+	// For pixels outside of the central block (relative to its top-left corner):
+	// the neighboring block's surface, and the pixel relative to that block.
+	fn neighbor_at(&self, chunk: TileKey, pix: Int2) -> Option<(&Surface, Int2)> {
+		let w = GRID as i32;
+		if pix.0 >= 0 && pix.0 < w && pix.1 >= 0 && pix.1 < w {
+			return None;
+		}
+		let tile = |c: i32| {
+			if c < 0 {
+				-1
+			} else if c < w {
+				0
+			} else {
+				1
+			}
+		};
+		let (tx, ty) = (tile(pix.0), tile(pix.1));
+		let blk = chunk.at(tx, ty);
+		let local = Int2(pix.0 - tx * w, pix.1 - ty * w);
+		Some((&self.palette[blk as usize], local))
+	}
+
 	/// Surface height at absolute position.
 	#[inline]
 	fn height_at_pos(&self, chunk: TileKey, pos: Vec2) -> f64 {
 		let (tile, uv) = Self::pos_to_tile(pos);
 		let blk = chunk.blocks[tile.1][tile.0];
 
-		if chunk.goody != 0 && tile == Usize2(1, 1) {
+		if chunk.goody != 0 && tile == Usize2(TILE_KEY_RADIUS, TILE_KEY_RADIUS) {
 			let bg = self.palette[blk as usize].height_at_uv(uv);
 			let fg = self.palette[chunk.goody as usize].height_at_uv(uv);
 			return max(fg, bg);
@@ -530,11 +972,12 @@ fn test_intersects() {
 
 	let b = SharedData::new(Arc::new(pal), Lights::new());
 	let mut chunk = TileKey::new();
-	chunk.blocks[0][0] = 1;
-	chunk.blocks[1][1] = 1;
+	chunk.set(-1, -1, 1);
+	chunk.set(0, 0, 1);
 	//([[1, 0, 0], [0, 1, 0], [0, 0, 0]]);
 
-	let start = Vector(1.25, 1.5, 0.0);
+	let c = TILE_KEY_RADIUS as f64;
+	let start = Vector(c + 0.25, c + 0.5, 0.0);
 	assert!(!b.intersects(chunk, &Ray::new(start, Vec3(0.0, 0.0, 1.0))));
 	assert!(b.intersects(chunk, &Ray::new(start, Vec3(0.0, 1.0, 0.0)))); // note: degenerate
 	assert!(b.intersects(chunk, &Ray::new(start, Vec3(1.0, 0.0, 0.0))));
@@ -542,6 +985,230 @@ fn test_intersects() {
 	assert!(!b.intersects(chunk, &Ray::new(start, Vec3(1.0, 0.0, 2.0).normalized())));
 }
 
+#[test]
+fn test_marchers() {
+	// the Packet marcher renders (nearly) the same as the Scalar reference.
+	let w = GRID as i32;
+	let c = w as f64 / 2.0;
+	let pal: Vec<Surface> = vec![
+		Surface::from_fn((w, w), |_, _| 0),
+		Surface::from_fn((w, w), |_, _| 20),
+		Surface::from_fn((w, w), |x, y| {
+			let r2 = ((x as f64 - c).powi(2) + (y as f64 - c).powi(2)) / (c * c);
+			(250.0 * (1.0 - r2).max(0.0).sqrt()) as u8
+		}),
+		Surface::from_fn((w, w), |x, _y| if x > w / 2 { 255 } else { 0 }),
+	];
+	let mut key = TileKey::with_center(1);
+	key.set(-1, -1, 3);
+	key.set(0, -1, 2);
+	key.set(-1, 0, 2);
+	key.goody = 2;
+
+	let mut b = SharedData::new(Arc::new(pal), Lights::new());
+	let packet = b.render_central_block(key);
+	b.set_marcher(Marcher::Scalar);
+	let scalar = b.render_central_block(key);
+
+	let diff: Vec<i32> = packet
+		.pixels()
+		.iter()
+		.zip(scalar.pixels())
+		.map(|(p, s)| (p.g() as i32 - s.g() as i32).abs())
+		.collect();
+	let mean = diff.iter().sum::<i32>() as f64 / diff.len() as f64;
+	assert!(mean < 0.1);
+	assert!(diff.iter().all(|&d| d <= 16));
+}
+
+#[test]
+fn test_render_rows() {
+	// bands of rows stitch together into the whole tile, also when denoised.
+	let w = GRID as i32;
+	let pal: Vec<Surface> = vec![
+		Surface::from_fn((w, w), |_, _| 0),
+		Surface::from_fn((w, w), |x, y| ((x * y) % 200) as u8),
+	];
+	let mut lights = Lights::new();
+	lights.sun_rays = 1;
+	lights.ambient_rays = 2;
+	lights.denoise = Denoise::Fast;
+	let b = SharedData::new(Arc::new(pal), lights);
+	let mut key = TileKey::with_center(1);
+	key.set(1, 1, 1);
+
+	let whole = b.render_central_block(key);
+	let mut stitched = Image::new((w, w));
+	for &(y0, y1) in &[(0, 7), (7, 40), (40, w)] {
+		let band = b.render_rows(key, y0, y1);
+		assert_eq!(band.dimensions(), (w, y1 - y0));
+		stitched.paste((0, y0), &band);
+	}
+	assert_eq!(stitched, whole);
+}
+
+#[test]
+fn test_denoise_seam() {
+	// a ramp running up across two tiles: the left block is strictly below the right one,
+	// but the right tile's denoise margin shows it, so canonicalize must keep it.
+	let w = GRID as i32;
+	let ramp = |h0: i32| {
+		Surface::new(
+			Image::from_fn((w, w), |x, _| (h0 + x) as u8),
+			Image::from_fn((w, w), |_, _| BGRA(200, 200, 200, 255)),
+		)
+	};
+	let pal = vec![Surface::from_fn((w, w), |_, _| 0), ramp(0), ramp(w)];
+	let mut lights = Lights::new();
+	lights.sun_rays = 1;
+	lights.ambient_rays = 4;
+	lights.denoise = Denoise::Smooth;
+	let mut low = TileKey::with_center(1);
+	low.set(1, 0, 2);
+	let mut high = TileKey::with_center(2);
+	high.set(-1, 0, 1);
+
+	// the seam is not exact (the margin is lit with the tile's own samples, see render_denoised),
+	// so average over a few seeds. Without the left block, the mean is about 0.28.
+	let mut seam = 0.0;
+	for seed in 0..8 {
+		let mut l = lights.clone();
+		l.seed = seed;
+		let mut r = Renderer::new(pal.clone(), l);
+		assert_eq!(r.canonicalize(high), high);
+		let tex = r.render_tiles_blocking(&[low, high]);
+		seam += seam_x(tex[0].image(), tex[1].image()) / 8.0;
+	}
+	assert!(seam < 0.22, "{}", seam);
+}
+
+#[test]
+fn test_bake_error() {
+	// a panic while baking shows an error texture, and leaves the bakery working.
+	let w = GRID as i32;
+	let pal: Vec<Surface> = vec![
+		Surface::from_fn((w, w), |_, _| 0),
+		Surface::from_fn((w, w), |_, _| 20),
+	];
+	let mut lights = Lights::new();
+	lights.sun_rays = 1;
+	lights.ambient_rays = 1;
+	let mut r = Renderer::new(pal, lights);
+	let mut bad = TileKey::with_center(1);
+	bad.goody = 7; // not in the palette
+
+	let tex = r.render_tiles_blocking(&[bad]);
+	assert_eq!(tex[0].image(), &error_image());
+	assert_eq!(r.stats().errors, 1);
+	assert_eq!(r.stats().queued, 0);
+
+	let tex = r.render_tiles_blocking(&[TileKey::with_center(1)]);
+	assert_ne!(tex[0].image(), &error_image());
+	assert_eq!(r.stats().errors, 1);
+}
+
+#[test]
+fn test_bake_timeout() {
+	// a tile that times out shows an error texture, until its late result replaces it.
+	let w = GRID as i32;
+	let pal: Vec<Surface> = vec![
+		Surface::from_fn((w, w), |_, _| 0),
+		Surface::from_fn((w, w), |x, y| (x ^ y) as u8),
+	];
+	let mut r = Renderer::new(pal, Lights::new());
+	r.bakery.timeout = Duration::from_millis(1);
+	let k = TileKey::with_center(1);
+	let poll = |r: &mut Renderer, error: bool| {
+		for _ in 0..10000 {
+			if (r.render_tile(k).image() == &error_image()) == error {
+				return;
+			}
+			std::thread::sleep(Duration::from_millis(1));
+		}
+		panic!("still error: {}", !error);
+	};
+
+	poll(&mut r, true);
+	assert_eq!(r.stats().errors, 1);
+	poll(&mut r, false);
+	assert_eq!(r.stats().errors, 0);
+	assert_eq!(r.stats().queued, 0);
+	assert!(r.late.is_empty());
+}
+
+#[test]
+fn test_invalidate() {
+	let w = GRID as i32;
+	let flat = |h: u8, c: u8| {
+		Surface::new(
+			Image::from_fn((w, w), |_, _| h),
+			Image::from_fn((w, w), |_, _| BGRA(c, c, c, 255)),
+		)
+	};
+	let pal = vec![flat(0, 0), flat(10, 100), flat(20, 200)];
+	let mut lights = Lights::new();
+	lights.sun_rays = 1;
+	lights.ambient_rays = 1;
+	let mut r = Renderer::new(pal, lights.clone());
+	let (a, b) = (TileKey::with_center(1), TileKey::with_center(2));
+	let bake = |r: &mut Renderer, k| r.render_tiles_blocking(&[k])[0].image().clone();
+	let (img_a, img_b) = (bake(&mut r, a), bake(&mut r, b));
+
+	// replacing a surface drops only the tiles that use it.
+	r.set_surface(2, flat(20, 50));
+	assert_eq!(r.stats().cached, 1);
+	assert_eq!(bake(&mut r, a), img_a);
+	assert_ne!(bake(&mut r, b), img_b);
+
+	// a tile being baked is cancelled, and baked again with the new surface.
+	r.render_tile(b);
+	r.set_surface(2, flat(20, 200));
+	assert_eq!(bake(&mut r, b), img_b);
+
+	// a surface that gets higher may shadow tiles that do not show it (yet),
+	// canonicalize drops it from their keys as long as it is lower.
+	r.set_surface(2, flat(5, 200));
+	assert_eq!(r.stats().cached, 1);
+	bake(&mut r, b);
+	r.set_surface(2, flat(20, 200));
+	assert_eq!(r.stats().cached, 0);
+	assert_eq!(bake(&mut r, a), img_a);
+
+	// all tiles depend on the lights.
+	lights.sun_intens = RGBf(0.0, 0.0, 0.0);
+	r.set_lights(lights.clone());
+	assert_eq!(r.stats().cached, 0);
+	assert!(r.lights() == lights);
+	assert_ne!(bake(&mut r, a), img_a);
+}
+
+#[test]
+fn test_canonicalize() {
+	let w = GRID as i32;
+	let pal = || {
+		vec![
+			Surface::default(),
+			Surface::from_fn((w, w), |_, _| 10),
+			Surface::from_fn((w, w), |_, _| 200),
+		]
+	};
+	let mut key = TileKey::with_center(1);
+	key.set(-1, 0, 1); // not higher than center: cannot cast shadows
+	key.set(1, 0, 2);
+	key.set(2, 2, 2);
+
+	let mut lights = Lights::new();
+	let r = Renderer::new(pal(), lights.clone());
+	let mut want = TileKey::with_center(1);
+	want.set(1, 0, 2);
+	assert_eq!(r.canonicalize(key), want);
+
+	lights.neighborhood = 2;
+	let r = Renderer::new(pal(), lights);
+	want.set(2, 2, 2);
+	assert_eq!(r.canonicalize(key), want);
+}
+
 //fn test_shade_pix() {
 //	let pal = default_palette();
 //	let lights = Lights::new();
@@ -556,12 +1223,12 @@ fn test_intersects() {
 
 #[test]
 fn test_try_recv() {
-	let pal = default_palette();
+	let pal = load_palette(Path::new(DEFAULT_TEXTURE_DIR)).unwrap();
 	let mut lights = Lights::new();
 	lights.sun_rays = 1;
 	lights.ambient_rays = 1;
 
-	let mut b = Bakery::new(Arc::new(pal), lights);
+	let mut b = Bakery::new(Arc::new(pal), lights, 0);
 
 	let key = TileKey::with_center(2);
 	b.send(key);
@@ -0,0 +1,93 @@
+use crate::prelude::*;
+
+/// Visible discontinuity at the seam between horizontally adjacent tiles
+/// (left's right edge touches right's left edge).
+///
+/// This is the average brightness jump across the seam (0-255),
+/// in excess of the brightness change between adjacent pixels on either side of it.
+/// So smooth shading (or noise) that runs across the seam counts as 0,
+/// while e.g. a shadow that stops at the seam does not.
+pub fn seam_x(left: &Image<BGRA>, right: &Image<BGRA>) -> f64 {
+	let (w, h) = left.dimensions();
+	assert_eq!(h, right.dimensions().1, "seam_x: tiles differ in height");
+	let l = |x, y| left.at((x, y)).luminance() as f64;
+	let r = |x, y| right.at((x, y)).luminance() as f64;
+
+	let mut excess = 0.0;
+	for y in 0..h {
+		let jump = (l(w - 1, y) - r(0, y)).abs();
+		let inside = ((l(w - 1, y) - l(w - 2, y)).abs() + (r(1, y) - r(0, y)).abs()) / 2.0;
+		excess += jump - inside;
+	}
+	max(0.0, excess / (h as f64))
+}
+
+/// Like seam_x, for vertically adjacent tiles (top's bottom edge touches bottom's top edge).
+pub fn seam_y(top: &Image<BGRA>, bottom: &Image<BGRA>) -> f64 {
+	seam_x(&transpose(top), &transpose(bottom))
+}
+
+fn transpose(img: &Image<BGRA>) -> Image<BGRA> {
+	let (w, h) = img.dimensions();
+	Image::from_fn((h, w), |x, y| img.at((y, x)))
+}
+
+#[test]
+fn test_seam() {
+	let gray = |v: u8| BGRA(v, v, v, 255);
+
+	// a smooth gradient across the seam is seamless.
+	let left = Image::from_fn((8, 4), |x, _| gray(10 * x as u8));
+	let right = Image::from_fn((8, 4), |x, _| gray(80 + 10 * x as u8));
+	assert_eq!(seam_x(&left, &right), 0.0);
+
+	// a step at the seam is not.
+	let right = Image::from_fn((8, 4), |x, _| gray(150 + 10 * x as u8));
+	assert_eq!(seam_x(&left, &right), 70.0);
+
+	let (top, bottom) = (transpose(&left), transpose(&right));
+	assert_eq!(seam_y(&top, &bottom), 70.0);
+}
+
+#[test]
+fn test_neighborhood_seam() {
+	// A low sun from the right casts a long shadow from a wall (block 2) over two floor tiles (block 1):
+	//
+	//   [1][1][2]
+	//
+	// With a neighborhood of 1, the leftmost tile does not see the wall,
+	// so the shadow is cut off at the seam. A neighborhood of 2 fixes that.
+	let w = GRID as i32;
+	let flat = |h: u8| {
+		Surface::new(
+			Image::from_fn((w, w), |_, _| h),
+			Image::from_fn((w, w), |_, _| BGRA(200, 200, 200, 255)),
+		)
+	};
+	let palette = Arc::new(vec![flat(0), flat(0), flat(255)]);
+
+	let mut lights = Lights::new();
+	lights.sun_dir = Vec3(1.0, 0.0, 0.3).normalized();
+	lights.sun_angle = 0.0;
+	lights.ambient_rays = 0;
+
+	let mut left = TileKey::with_center(1);
+	left.set(1, 0, 1);
+	left.set(2, 0, 2);
+	let mut middle = TileKey::with_center(1);
+	middle.set(-1, 0, 1);
+	middle.set(1, 0, 2);
+
+	let seam = |neighborhood: usize| {
+		let mut l = lights.clone();
+		l.neighborhood = neighborhood;
+		let r = SharedData::new(palette.clone(), l);
+		seam_x(
+			&r.render_central_block(left),
+			&r.render_central_block(middle),
+		)
+	};
+	let (near, far) = (seam(1), seam(2));
+	assert!(near > 10.0, "{}", near);
+	assert!(far < 1.0, "{}", far);
+}
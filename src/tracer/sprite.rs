@@ -0,0 +1,114 @@
+use crate::prelude::*;
+
+/// Ray-trace lighting for a dynamic sprite (hamster, enemies, ...),
+/// with the same Lights as the level's tiles.
+///
+/// Unlike tiles, which are baked with their neighboring blocks (see TileKey), sprites move around.
+/// So they only shadow themselves, not the map (see drop_shadow), nor are they shadowed by it.
+/// Cheap enough to shade each animation frame once, when a level is loaded.
+pub fn shade_sprite(surf: &Surface, lights: &Lights) -> Image<BGRA> {
+	Image::from_fn(surf.dimensions(), |x, y| {
+		shade_sprite_pix(surf, lights, Int2(x, y))
+	})
+}
+
+fn shade_sprite_pix(surf: &Surface, lights: &Lights, pix: Int2) -> BGRA {
+	let dm = surf.diffuse_at(pix);
+	if dm.a() == 0 {
+		return dm;
+	}
+
+	let normal = surf.normal_at(pix);
+	let xy = Vec2(pix.x() as f64, pix.y() as f64) * (1.0 / GRID as f64);
+	let pos = Vector(xy.x(), xy.y(), surf.height_at(pix)) + 0.02 * normal;
+
+	let rnd = hash_scramble(&[lights.seed, pix.0 as u64, pix.1 as u64]);
+	let light = lights.light_at(normal, rnd, |dirs, hit| {
+		for (h, &d) in hit.iter_mut().zip(dirs.iter()) {
+			*h = self_intersects(surf, &Ray::new(pos, d));
+		}
+	});
+	dm.lit(light)
+}
+
+// Does the ray hit the sprite's own height map?
+// Rays leaving the sprite escape: the map behind it is further away.
+fn self_intersects(surf: &Surface, r: &Ray) -> bool {
+	if r.dir.z() <= 0.0 {
+		return true;
+	}
+
+	// advance ~0.7 pixels per step in the XY plane, like the tile renderer.
+	let stride = 0.7 / (r.dir.z().cos() * GRID as f64);
+	let (w, h) = surf.dimensions();
+	let maxh = surf.hm_max();
+	let mut t = stride;
+	loop {
+		t += stride;
+		let p = r.at(t);
+		if p.z() > maxh {
+			return false;
+		}
+		let x = (p.x() * GRID as f64) as i32;
+		let y = (p.y() * GRID as f64) as i32;
+		if p.x() < 0.0 || p.y() < 0.0 || x >= w || y >= h {
+			return false;
+		}
+		let pix = Int2(x, y);
+		if surf.diffuse_at(pix).a() != 0 && surf.height_at(pix) > p.z() {
+			return true;
+		}
+	}
+}
+
+/// Silhouette of a sprite, to be drawn on the map behind it as a cheap drop shadow.
+pub fn drop_shadow(dm: &Image<BGRA>) -> Image<BGRA> {
+	const OPACITY: f32 = 0.35;
+	Image::from_fn(dm.dimensions(), |x, y| {
+		BGRA(0, 0, 0, (dm.at((x, y)).a() as f32 * OPACITY) as u8)
+	})
+}
+
+/// Where the drop shadow of a sprite falls, relative to the sprite's position,
+/// for a sprite floating depth pixels in front of the map.
+/// The shadow is cast away from the sun.
+pub fn drop_shadow_offset(lights: &Lights, depth: f64) -> Pt {
+	let sun = lights.sun_dir;
+	if sun.z() <= 0.0 {
+		return Pt(0, 0);
+	}
+	let dx = -sun.x() / sun.z() * depth;
+	let dy = -sun.y() / sun.z() * depth;
+	Pt(dx.round() as i32, dy.round() as i32)
+}
+
+#[test]
+fn test_shade_sprite() {
+	// a flat, opaque sprite with a transparent corner.
+	let mut surf = Surface::from_fn((8, 8), |_, _| 0);
+	for p in surf.dm.pixels_mut() {
+		*p = BGRA(255, 255, 255, 255);
+	}
+	surf.dm[0][0] = BGRA(0, 0, 0, 0);
+
+	let img = shade_sprite(&surf, &Lights::new());
+	assert_eq!(img.at((0, 0)).a(), 0);
+	let c = img.at((4, 4));
+	assert_eq!(c.a(), 255);
+	// lit, but not brighter than the diffuse color.
+	assert!(c.r() > 0 && c.r() < 255);
+}
+
+#[test]
+fn test_drop_shadow() {
+	let mut dm = Image::<BGRA>::new((2, 1));
+	dm[0][1] = BGRA(10, 20, 30, 255);
+	let shadow = drop_shadow(&dm);
+	assert_eq!(shadow.at((0, 0)).a(), 0);
+	assert!(shadow.at((1, 0)).a() > 0);
+	assert_eq!(shadow.at((1, 0)).r(), 0);
+
+	// default sun: upper right, so shadow falls to the lower left.
+	let off = drop_shadow_offset(&Lights::new(), 8.0);
+	assert!(off.0 < 0 && off.1 > 0);
+}
@@ -1,6 +1,7 @@
 use crate::prelude::*;
 
 /// 3D texture.
+#[derive(Clone)]
 pub struct Surface {
 	/// Diffuse map
 	pub dm: Image<BGRA>,